@@ -1,14 +1,156 @@
-use std::path::{Path, PathBuf};
+use std::{
+    net::IpAddr,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
 
 use autoschematic_core::{
     connector::ResourceAddress,
     error_util::{invalid_addr, invalid_addr_path},
 };
 
+/// Per-file sidecars are stored beside the file they describe, E.G. `remotefs/host/etc/crontab.remotefs.ron`.
+pub const SIDECAR_SUFFIX: &str = ".remotefs.ron";
+
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}{}", path.to_string_lossy(), SIDECAR_SUFFIX))
+}
+
+pub fn is_sidecar(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(SIDECAR_SUFFIX)
+}
+
+/// A RemoteFsPath addresses a file on a remote host as
+/// `remotefs/<hostname>/<path>`, where `path` is the remote path with its root removed.
+///
+/// Remote POSIX paths map directly, E.G. `/etc/crontab` <=> `remotefs/host/etc/crontab`.
+/// Windows paths are encoded so that they survive a checkout on any OS:
+///  - Drive paths use the drive letter followed by `$` as the first component (like admin shares),
+///    E.G. `C:\ProgramData\app\config.ini` <=> `remotefs/host/C$/ProgramData/app/config.ini`.
+///  - UNC paths use `UNC$` as the first component,
+///    E.G. `\\server\share\app.ini` <=> `remotefs/host/UNC$/server/share/app.ini`.
+///
+/// The hostname segment names an entry in the config's `hosts` map. To manage two sshd
+/// instances on one machine, give them distinct entries, either as `name:port`
+/// (E.G. `remotefs/db-01:2222/etc/app.conf`) or as an alias with `hostname` set in its RemoteFsHost.
+/// Likewise, to manage one machine with several sets of credentials, use entries of the form `user@name`,
+/// E.G. `remotefs/appdeploy@web-01/opt/app/env` and `remotefs/root@web-01/etc/nginx/nginx.conf`.
+///
+/// Windows remote paths are rendered with forward slashes (E.G. `C:/ProgramData/app/config.ini`),
+/// which Windows accepts, and which lets them be compared component-wise against mount paths.
+///
+/// The hostname is validated and normalized to its ASCII (punycode) form, so that
+/// internationalized hostnames match their config entries however they are spelled.
+///
+/// A hostname segment of the form `@<group>` addresses a file broadcast to every host in one of
+/// the config's `groups`, E.G. `remotefs/@web/etc/motd`. Such a file is stored once in the repo,
+/// and is planned and applied against each member host.
 #[derive(Debug, Clone)]
 pub struct RemoteFsPath {
     pub hostname: String,
     pub path:     PathBuf,
+    /// The hostname segment as it appears in the repo, which may be a Unicode spelling of `hostname`.
+    host_segment: String,
+}
+
+/// Validate a hostname as used in addresses and config, and normalize it to the
+/// ASCII form used for lookups and for connecting. Internationalized names are converted to punycode.
+pub fn normalize_hostname(hostname: &str) -> Result<String, anyhow::Error> {
+    if hostname.is_empty() || hostname == "." || hostname == ".." {
+        bail!("Invalid hostname {:?}", hostname);
+    }
+    if hostname
+        .chars()
+        .any(|c| c == '/' || c == '\\' || c.is_whitespace() || c.is_control())
+    {
+        bail!("Invalid hostname {:?}", hostname);
+    }
+    if hostname.parse::<IpAddr>().is_ok() {
+        return Ok(hostname.to_string());
+    }
+    // A `user@name` segment disambiguates several sets of credentials for the same machine.
+    if let Some((user, name)) = hostname.split_once('@') {
+        let valid_user = user.chars().next().is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+            && user.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-' || c == '.');
+        if !valid_user {
+            bail!("Invalid username in hostname {:?}", hostname);
+        }
+        return Ok(format!("{}@{}", user, normalize_hostname(name)?));
+    }
+    // A `name:port` segment disambiguates several sshd instances on the same machine.
+    if let Some((name, port)) = hostname.rsplit_once(':')
+        && port.parse::<u16>().is_ok()
+    {
+        return Ok(format!("{}:{}", normalize_hostname(name)?, port));
+    }
+
+    let ascii = idna::domain_to_ascii(hostname).map_err(|e| anyhow!("Invalid hostname {:?}: {:?}", hostname, e))?;
+    if ascii.split('.').any(str::is_empty) {
+        bail!("Invalid hostname {:?}: empty label", hostname);
+    }
+    Ok(ascii)
+}
+
+/// Validate the name of a host group, which may contain only ASCII letters, digits, `-` and `_`.
+pub fn validate_group_name(group: &str) -> Result<&str, anyhow::Error> {
+    if group.is_empty() || !group.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("Invalid group name {:?}", group);
+    }
+    Ok(group)
+}
+
+impl RemoteFsPath {
+    /// The group this address is broadcast to, if it is of the form `remotefs/@<group>/...`.
+    pub fn group(&self) -> Option<&str> {
+        self.hostname.strip_prefix('@')
+    }
+
+    /// The address of this group-addressed file on one of the group's member hosts.
+    /// It still refers to the group's file in the repo.
+    pub fn for_member(&self, hostname: &str) -> Self {
+        RemoteFsPath {
+            hostname:     hostname.to_string(),
+            path:         self.path.clone(),
+            host_segment: self.host_segment.clone(),
+        }
+    }
+
+    /// Form the address of a file given its path on the remote host.
+    pub fn from_remote_path(hostname: &str, remote_path: &Path) -> Self {
+        let s = remote_path.to_string_lossy().replace('\\', "/");
+        let bytes = s.as_bytes();
+
+        let path = if let Some(rest) = s.strip_prefix("//") {
+            PathBuf::from("UNC$").join(rest)
+        } else if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+            let drive = format!("{}$", (bytes[0] as char).to_ascii_uppercase());
+            PathBuf::from(drive).join(s[2..].trim_start_matches('/'))
+        } else {
+            PathBuf::from(s.trim_start_matches('/'))
+        };
+
+        RemoteFsPath {
+            hostname: hostname.to_string(),
+            path,
+            host_segment: hostname.to_string(),
+        }
+    }
+
+    /// The path of this file on the remote host.
+    pub fn remote_path(&self) -> PathBuf {
+        let mut components = self.path.components().map(|c| c.as_os_str().to_string_lossy().to_string());
+        let first = components.next().unwrap_or_default();
+        let rest: Vec<String> = components.collect();
+
+        if first == "UNC$" {
+            PathBuf::from(format!("//{}", rest.join("/")))
+        } else if first.len() == 2 && first.as_bytes()[0].is_ascii_alphabetic() && first.ends_with('$') {
+            PathBuf::from(format!("{}:/{}", &first[..1], rest.join("/")))
+        } else {
+            PathBuf::from("/").join(&self.path)
+        }
+    }
 }
 
 impl ResourceAddress for RemoteFsPath {
@@ -20,7 +162,7 @@ impl ResourceAddress for RemoteFsPath {
         } else {
             &self.path
         };
-        PathBuf::from("remotefs").join(self.hostname.clone()).join(path)
+        PathBuf::from("remotefs").join(&self.host_segment).join(path)
     }
 
     fn from_path(path: &Path) -> Result<Self, anyhow::Error> {
@@ -38,12 +180,101 @@ impl ResourceAddress for RemoteFsPath {
             ["remotefs", hostname, ..] => {
                 let prefix = PathBuf::from("remotefs").join(hostname);
                 let local_path = path.strip_prefix(prefix)?;
+                // Don't let an address escape its host's directory.
+                if local_path.components().any(|c| !matches!(c, Component::Normal(_))) {
+                    return Err(invalid_addr_path(path));
+                }
+                let normalized = match hostname.strip_prefix('@') {
+                    Some(group) => format!("@{}", validate_group_name(group)?),
+                    None => normalize_hostname(hostname)?,
+                };
                 Ok(RemoteFsPath {
-                    hostname: hostname.to_string(),
-                    path:     local_path.to_path_buf(),
+                    hostname:     normalized,
+                    path:         local_path.to_path_buf(),
+                    host_segment: hostname.to_string(),
                 })
             }
             _ => Err(invalid_addr_path(path)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(remote_path: &str, repo_path: &str, rendered: &str) {
+        let addr = RemoteFsPath::from_remote_path("host", Path::new(remote_path));
+        assert_eq!(addr.to_path_buf(), PathBuf::from(repo_path));
+        let parsed = RemoteFsPath::from_path(Path::new(repo_path)).unwrap();
+        assert_eq!(parsed.path, addr.path);
+        assert_eq!(parsed.remote_path(), PathBuf::from(rendered));
+    }
+
+    #[test]
+    fn posix_paths_map_directly() {
+        round_trip("/etc/crontab", "remotefs/host/etc/crontab", "/etc/crontab");
+    }
+
+    #[test]
+    fn windows_paths_round_trip() {
+        round_trip(
+            "C:\\ProgramData\\app\\config.ini",
+            "remotefs/host/C$/ProgramData/app/config.ini",
+            "C:/ProgramData/app/config.ini",
+        );
+        round_trip("d:/logs/app.log", "remotefs/host/D$/logs/app.log", "D:/logs/app.log");
+        round_trip(
+            "\\\\server\\share\\app.ini",
+            "remotefs/host/UNC$/server/share/app.ini",
+            "//server/share/app.ini",
+        );
+    }
+
+    #[test]
+    fn malformed_paths_are_rejected() {
+        assert!(RemoteFsPath::from_path(Path::new("remotefs/host/../etc/passwd")).is_err());
+        assert!(RemoteFsPath::from_path(Path::new("other/host/etc/passwd")).is_err());
+        assert!(RemoteFsPath::from_path(Path::new("remotefs")).is_err());
+    }
+
+    #[test]
+    fn hostnames_are_normalized_to_ascii() {
+        assert_eq!(normalize_hostname("Web-01.Example.COM").unwrap(), "web-01.example.com");
+        assert_eq!(normalize_hostname("münchen.example").unwrap(), "xn--mnchen-3ya.example");
+        assert_eq!(normalize_hostname("xn--mnchen-3ya.example").unwrap(), "xn--mnchen-3ya.example");
+        assert_eq!(normalize_hostname("10.0.0.1").unwrap(), "10.0.0.1");
+        assert_eq!(normalize_hostname("fe80::1").unwrap(), "fe80::1");
+
+        let addr = RemoteFsPath::from_path(Path::new("remotefs/münchen.example/etc/motd")).unwrap();
+        assert_eq!(addr.hostname, "xn--mnchen-3ya.example");
+        assert_eq!(addr.to_path_buf(), PathBuf::from("remotefs/münchen.example/etc/motd"));
+    }
+
+    #[test]
+    fn malformed_hostnames_are_rejected() {
+        for hostname in ["", ".", "..", "web 01", "web\\01", "web..example", "web-01.", "web\t01"] {
+            assert!(normalize_hostname(hostname).is_err(), "accepted {:?}", hostname);
+        }
+        assert!(RemoteFsPath::from_path(Path::new("remotefs/web..example/etc/motd")).is_err());
+    }
+
+    #[test]
+    fn hostnames_may_carry_a_port() {
+        assert_eq!(normalize_hostname("DB-01:2222").unwrap(), "db-01:2222");
+        assert_eq!(normalize_hostname("münchen.example:22").unwrap(), "xn--mnchen-3ya.example:22");
+        assert_eq!(normalize_hostname("10.0.0.1:2222").unwrap(), "10.0.0.1:2222");
+        assert_eq!(normalize_hostname("::1").unwrap(), "::1");
+
+        let addr = RemoteFsPath::from_path(Path::new("remotefs/db-01:2222/etc/app.conf")).unwrap();
+        assert_eq!(addr.hostname, "db-01:2222");
+        assert_eq!(addr.remote_path(), PathBuf::from("/etc/app.conf"));
+    }
+
+    #[test]
+    fn ports_need_a_valid_hostname() {
+        assert!(normalize_hostname(":2222").is_err());
+        assert!(normalize_hostname("web..example:2222").is_err());
+        assert!(normalize_hostname("web 01:2222").is_err());
+    }
+}
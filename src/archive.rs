@@ -0,0 +1,46 @@
+use sha2::{Digest, Sha256};
+
+/// Compute a checksum over the tree contained in a tar archive.
+/// The checksum covers paths, file contents and symlink targets, but not entry order,
+/// timestamps or ownership, so two archives of the same tree built on
+/// different machines will compare equal.
+pub fn tree_checksum(archive: &[u8]) -> Result<String, anyhow::Error> {
+    let mut tar = tar::Archive::new(archive);
+    let mut entries = Vec::new();
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        let path = path.trim_start_matches("./").trim_end_matches('/').to_string();
+        if path.is_empty() || path == "." {
+            continue;
+        }
+
+        let kind = entry.header().entry_type();
+        let digest = if kind.is_file() {
+            let mut hasher = Sha256::new();
+            std::io::copy(&mut entry, &mut hasher)?;
+            format!("file:{:x}", hasher.finalize())
+        } else if kind.is_symlink() {
+            let target = entry.link_name()?.map(|l| l.to_string_lossy().to_string());
+            format!("symlink:{}", target.unwrap_or_default())
+        } else if kind.is_dir() {
+            String::from("dir")
+        } else {
+            continue;
+        };
+
+        entries.push((path, digest));
+    }
+
+    entries.sort();
+
+    let mut hasher = Sha256::new();
+    for (path, digest) in entries {
+        hasher.update(path.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(digest.as_bytes());
+        hasher.update(b"\n");
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
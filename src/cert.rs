@@ -0,0 +1,209 @@
+use std::{path::Path, time::SystemTime};
+
+use anyhow::{Context, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+/// The certificate type of a user certificate, as opposed to a host certificate.
+const SSH_CERT_TYPE_USER: u32 = 1;
+
+/// The parts of an OpenSSH user certificate (see PROTOCOL.certkeys) that decide whether it may be used.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub key_type:     String,
+    pub key_id:       String,
+    /// The usernames the certificate is valid for. Empty means any.
+    pub principals:   Vec<String>,
+    pub valid_after:  u64,
+    pub valid_before: u64,
+}
+
+/// Reads the SSH wire encoding of a certificate.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], anyhow::Error> {
+        if self.0.len() < n {
+            bail!("Certificate is truncated");
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, anyhow::Error> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, anyhow::Error> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8], anyhow::Error> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn utf8(&mut self) -> Result<String, anyhow::Error> {
+        Ok(String::from_utf8(self.string()?.to_vec())?)
+    }
+}
+
+impl Certificate {
+    /// Parse a certificate in OpenSSH format, E.G. `ssh-ed25519-cert-v01@openssh.com AAAA... ops@example.com`.
+    pub fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        let Some(blob) = s.split_whitespace().nth(1) else {
+            bail!("Expected `<type> <base64> [comment]`");
+        };
+        let blob = STANDARD.decode(blob)?;
+        let mut reader = Reader(&blob);
+
+        let key_type = reader.utf8()?;
+        // The public key fields that follow the nonce differ by key type.
+        let key_fields = match key_type.as_str() {
+            "ssh-ed25519-cert-v01@openssh.com" => 1,
+            "ssh-rsa-cert-v01@openssh.com"
+            | "ecdsa-sha2-nistp256-cert-v01@openssh.com"
+            | "ecdsa-sha2-nistp384-cert-v01@openssh.com"
+            | "ecdsa-sha2-nistp521-cert-v01@openssh.com"
+            | "sk-ssh-ed25519-cert-v01@openssh.com" => 2,
+            "sk-ecdsa-sha2-nistp256-cert-v01@openssh.com" => 3,
+            "ssh-dss-cert-v01@openssh.com" => 4,
+            _ => bail!("{} is not an OpenSSH certificate type", key_type),
+        };
+        let _nonce = reader.string()?;
+        for _ in 0..key_fields {
+            reader.string()?;
+        }
+        let _serial = reader.u64()?;
+        if reader.u32()? != SSH_CERT_TYPE_USER {
+            bail!("This is a host certificate, not a user certificate");
+        }
+        let key_id = reader.utf8()?;
+        let mut principals = Vec::new();
+        let mut packed = Reader(reader.string()?);
+        while !packed.0.is_empty() {
+            principals.push(packed.utf8()?);
+        }
+        let valid_after = reader.u64()?;
+        let valid_before = reader.u64()?;
+
+        Ok(Certificate {
+            key_type,
+            key_id,
+            principals,
+            valid_after,
+            valid_before,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        let body = std::fs::read_to_string(path).with_context(|| format!("Reading certificate {}", path.display()))?;
+        Self::parse(&body).with_context(|| format!("Parsing certificate {}", path.display()))
+    }
+
+    /// Check that the certificate may be used to log in as `username` now,
+    /// so that an expired certificate fails with a clear error rather than as a bare authentication failure.
+    pub fn check(&self, username: &str) -> Result<(), anyhow::Error> {
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs();
+        if now < self.valid_after {
+            bail!("Certificate {} is not valid for another {}s", self.key_id, self.valid_after - now);
+        }
+        if now >= self.valid_before {
+            bail!("Certificate {} expired {}s ago", self.key_id, now - self.valid_before);
+        }
+        if !self.principals.is_empty() && !self.principals.iter().any(|principal| principal == username) {
+            bail!(
+                "Certificate {} is not valid for user {}, only for {}",
+                self.key_id,
+                username,
+                self.principals.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519_CERT: &str = "ssh-ed25519-cert-v01@openssh.com";
+
+    fn put_string(blob: &mut Vec<u8>, s: &[u8]) {
+        blob.extend_from_slice(&(s.len() as u32).to_be_bytes());
+        blob.extend_from_slice(s);
+    }
+
+    /// The wire encoding of an ed25519 certificate, up to and including the fields that parse reads.
+    fn cert_blob(cert_type: u32, principals: &[&str], valid_after: u64, valid_before: u64) -> Vec<u8> {
+        let mut blob = Vec::new();
+        put_string(&mut blob, ED25519_CERT.as_bytes());
+        put_string(&mut blob, &[7; 32]);
+        put_string(&mut blob, &[9; 32]);
+        blob.extend_from_slice(&42u64.to_be_bytes());
+        blob.extend_from_slice(&cert_type.to_be_bytes());
+        put_string(&mut blob, b"ops@example.com");
+        let mut packed = Vec::new();
+        for principal in principals {
+            put_string(&mut packed, principal.as_bytes());
+        }
+        put_string(&mut blob, &packed);
+        blob.extend_from_slice(&valid_after.to_be_bytes());
+        blob.extend_from_slice(&valid_before.to_be_bytes());
+        blob
+    }
+
+    fn encode(blob: &[u8]) -> String {
+        format!("{} {} ops@example.com", ED25519_CERT, STANDARD.encode(blob))
+    }
+
+    #[test]
+    fn user_certificates_parse() {
+        let mut blob = cert_blob(SSH_CERT_TYPE_USER, &["deploy", "root"], 1000, 2000);
+        let cert = Certificate::parse(&encode(&blob)).unwrap();
+        assert_eq!(cert.key_type, ED25519_CERT);
+        assert_eq!(cert.key_id, "ops@example.com");
+        assert_eq!(cert.principals, ["deploy", "root"]);
+        assert_eq!((cert.valid_after, cert.valid_before), (1000, 2000));
+
+        // Critical options, extensions, the reserved field, the signature key and the signature aren't read.
+        for _ in 0..5 {
+            put_string(&mut blob, b"");
+        }
+        assert_eq!(Certificate::parse(&encode(&blob)).unwrap().principals, ["deploy", "root"]);
+    }
+
+    #[test]
+    fn truncated_certificates_are_rejected() {
+        let blob = cert_blob(SSH_CERT_TYPE_USER, &["deploy"], 0, u64::MAX);
+        for len in 0..blob.len() {
+            assert!(Certificate::parse(&encode(&blob[..len])).is_err(), "parsed {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn other_keys_are_rejected() {
+        let host = cert_blob(2, &["web-01.example.com"], 0, u64::MAX);
+        assert!(Certificate::parse(&encode(&host)).is_err());
+
+        let mut plain = Vec::new();
+        put_string(&mut plain, b"ssh-ed25519");
+        put_string(&mut plain, &[9; 32]);
+        assert!(Certificate::parse(&encode(&plain)).is_err());
+
+        assert!(Certificate::parse(ED25519_CERT).is_err());
+        assert!(Certificate::parse(&format!("{} not-base64!", ED25519_CERT)).is_err());
+    }
+
+    #[test]
+    fn check_enforces_validity_and_principals() {
+        let parse = |principals: &[&str], valid_after, valid_before| {
+            Certificate::parse(&encode(&cert_blob(SSH_CERT_TYPE_USER, principals, valid_after, valid_before))).unwrap()
+        };
+        assert!(parse(&["deploy"], 0, u64::MAX).check("deploy").is_ok());
+        assert!(parse(&["deploy"], 0, u64::MAX).check("root").is_err());
+        assert!(parse(&[], 0, u64::MAX).check("root").is_ok());
+        assert!(parse(&[], 0, 1).check("root").is_err());
+        assert!(parse(&[], u64::MAX - 1, u64::MAX).check("root").is_err());
+    }
+}
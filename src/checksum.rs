@@ -0,0 +1,156 @@
+use std::io::Read;
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::config::RemoteFsChecksum;
+
+impl RemoteFsChecksum {
+    /// The name of this algorithm, as used in digest stubs and plan output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RemoteFsChecksum::Sha256 => "sha256",
+            RemoteFsChecksum::Blake3 => "blake3",
+            RemoteFsChecksum::Xxh128 => "xxh128",
+            RemoteFsChecksum::Md5 => "md5",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sha256" => Some(RemoteFsChecksum::Sha256),
+            "blake3" => Some(RemoteFsChecksum::Blake3),
+            "xxh128" => Some(RemoteFsChecksum::Xxh128),
+            "md5" => Some(RemoteFsChecksum::Md5),
+            _ => None,
+        }
+    }
+
+    /// The command that computes this checksum on the remote host, in the style of sha256sum.
+    pub fn tool(&self) -> &'static str {
+        match self {
+            RemoteFsChecksum::Sha256 => "sha256sum",
+            RemoteFsChecksum::Blake3 => "b3sum",
+            RemoteFsChecksum::Xxh128 => "xxh128sum",
+            RemoteFsChecksum::Md5 => "md5sum",
+        }
+    }
+
+    /// The length of a digest in hex digits.
+    pub fn hex_len(&self) -> usize {
+        match self {
+            RemoteFsChecksum::Sha256 | RemoteFsChecksum::Blake3 => 64,
+            RemoteFsChecksum::Xxh128 | RemoteFsChecksum::Md5 => 32,
+        }
+    }
+
+    /// Compute the digest of `data` locally, as the remote tool would print it.
+    pub fn digest(&self, data: &[u8]) -> String {
+        match self {
+            RemoteFsChecksum::Sha256 => format!("{:x}", Sha256::digest(data)),
+            RemoteFsChecksum::Blake3 => blake3::hash(data).to_hex().to_string(),
+            RemoteFsChecksum::Xxh128 => format!("{:032x}", xxhash_rust::xxh3::xxh3_128(data)),
+            RemoteFsChecksum::Md5 => format!("{:x}", Md5::digest(data)),
+        }
+    }
+
+    /// Compute the digest of everything read from `reader`, `chunk_size` bytes at a time,
+    /// so that a large file can be hashed without holding it in memory.
+    pub fn digest_reader(&self, reader: &mut impl Read, chunk_size: usize) -> std::io::Result<String> {
+        fn each_chunk(reader: &mut impl Read, chunk_size: usize, mut f: impl FnMut(&[u8])) -> std::io::Result<()> {
+            let mut buf = vec![0u8; chunk_size];
+            loop {
+                let n = reader.read(&mut buf)?;
+                if n == 0 {
+                    return Ok(());
+                }
+                f(&buf[..n]);
+            }
+        }
+
+        Ok(match self {
+            RemoteFsChecksum::Sha256 => {
+                let mut hasher = Sha256::new();
+                each_chunk(reader, chunk_size, |chunk| hasher.update(chunk))?;
+                format!("{:x}", hasher.finalize())
+            }
+            RemoteFsChecksum::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                each_chunk(reader, chunk_size, |chunk| {
+                    hasher.update(chunk);
+                })?;
+                hasher.finalize().to_hex().to_string()
+            }
+            RemoteFsChecksum::Xxh128 => {
+                let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+                each_chunk(reader, chunk_size, |chunk| hasher.update(chunk))?;
+                format!("{:032x}", hasher.digest128())
+            }
+            RemoteFsChecksum::Md5 => {
+                let mut hasher = Md5::new();
+                each_chunk(reader, chunk_size, |chunk| hasher.update(chunk))?;
+                format!("{:x}", hasher.finalize())
+            }
+        })
+    }
+
+    /// Extract the digest from a line of the remote tool's output, E.G. `9f86d081...  /etc/motd`.
+    pub fn parse_output(&self, line: &str) -> Option<String> {
+        line.split_whitespace()
+            .next()
+            .filter(|digest| digest.len() == self.hex_len() && digest.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(|digest| digest.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [RemoteFsChecksum; 4] = [
+        RemoteFsChecksum::Sha256,
+        RemoteFsChecksum::Blake3,
+        RemoteFsChecksum::Xxh128,
+        RemoteFsChecksum::Md5,
+    ];
+
+    #[test]
+    fn parses_tool_output() {
+        let sha256 = RemoteFsChecksum::Sha256;
+        let digest = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        assert_eq!(sha256.parse_output(&format!("{}  /etc/motd", digest)).as_deref(), Some(digest));
+        assert_eq!(sha256.parse_output(&format!("{} */etc/motd\n", digest.to_uppercase())).as_deref(), Some(digest));
+        // The wrong length for the algorithm, E.G. md5sum output read as sha256.
+        assert_eq!(sha256.parse_output("d41d8cd98f00b204e9800998ecf8427e  /etc/motd"), None);
+        assert_eq!(sha256.parse_output("sha256sum: /etc/motd: No such file or directory"), None);
+        assert_eq!(sha256.parse_output(""), None);
+    }
+
+    #[test]
+    fn digests_match_known_values() {
+        assert_eq!(
+            RemoteFsChecksum::Sha256.digest(b"test"),
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        );
+        assert_eq!(RemoteFsChecksum::Md5.digest(b""), "d41d8cd98f00b204e9800998ecf8427e");
+    }
+
+    #[test]
+    fn digests_parse_as_their_own_output() {
+        for checksum in ALL {
+            let digest = checksum.digest(b"hello\n");
+            assert_eq!(digest.len(), checksum.hex_len(), "{}", checksum.name());
+            assert_eq!(checksum.parse_output(&format!("{}  -", digest)), Some(digest));
+            assert_eq!(RemoteFsChecksum::from_name(checksum.name()), Some(checksum));
+        }
+    }
+
+    #[test]
+    fn reader_digests_match_whole_digests() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        for checksum in ALL {
+            let digest = checksum.digest_reader(&mut data.as_slice(), 4096).unwrap();
+            assert_eq!(digest, checksum.digest(&data), "{}", checksum.name());
+        }
+    }
+}
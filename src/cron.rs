@@ -0,0 +1,218 @@
+use anyhow::{Context, bail};
+
+/// A five-field cron expression (`minute hour day-of-month month day-of-week`), as in crontab(5).
+/// Each field is `*`, a value, a range `a-b`, any of those with a step `/n`, or a comma-separated list of them.
+/// Day-of-week runs from 0 (Sunday) to 7 (Sunday again). As in cron, if both day fields are restricted,
+/// a time matches if either does.
+#[derive(Debug, Clone)]
+pub struct CronSchedule {
+    minutes:      Vec<bool>,
+    hours:        Vec<bool>,
+    days:         Vec<bool>,
+    months:       Vec<bool>,
+    weekdays:     Vec<bool>,
+    /// Whether the day-of-month and day-of-week fields are `*`.
+    days_any:     bool,
+    weekdays_any: bool,
+}
+
+/// Parse one cron field into a table of the values in `min..=max` that it matches.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>, anyhow::Error> {
+    let mut matches = vec![false; max as usize + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().with_context(|| format!("Invalid step in {}", part))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            bail!("Invalid step in {}", part);
+        }
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                // `5/15` means every 15 from 5.
+                None if part.contains('/') => (range.parse()?, max),
+                None => {
+                    let value = range.parse()?;
+                    (value, value)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            bail!("{} is out of range {}-{}", part, min, max);
+        }
+        for value in (start..=end).step_by(step as usize) {
+            matches[value as usize] = true;
+        }
+    }
+    Ok(matches)
+}
+
+/// The civil date for a count of days since 1970-01-01, as (year, month, day).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // From Howard Hinnant's date algorithms.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self, anyhow::Error> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("Expected 5 fields in cron expression `{}`, found {}", expr, fields.len());
+        };
+        let field = |field: &str, min: u32, max: u32| {
+            parse_field(field, min, max).with_context(|| format!("In cron expression `{}`", expr))
+        };
+        let mut weekdays = field(weekday, 0, 7)?;
+        if weekdays[7] {
+            weekdays[0] = true;
+        }
+        Ok(CronSchedule {
+            minutes: field(minute, 0, 59)?,
+            hours: field(hour, 0, 23)?,
+            days: field(day, 1, 31)?,
+            months: field(month, 1, 12)?,
+            weekdays,
+            days_any: day == "*",
+            weekdays_any: weekday == "*",
+        })
+    }
+
+    /// Whether the minute containing `unix_time`, in a timezone `utc_offset` seconds east of UTC, matches.
+    pub fn matches(&self, unix_time: i64, utc_offset: i32) -> bool {
+        let local = unix_time + utc_offset as i64;
+        let days = local.div_euclid(86400);
+        let secs = local.rem_euclid(86400);
+        let (_, month, day) = civil_from_days(days);
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4).rem_euclid(7) as usize;
+
+        let day_matches = match (self.days_any, self.weekdays_any) {
+            (true, true) => true,
+            (false, true) => self.days[day as usize],
+            (true, false) => self.weekdays[weekday],
+            (false, false) => self.days[day as usize] || self.weekdays[weekday],
+        };
+        self.minutes[(secs / 60 % 60) as usize]
+            && self.hours[(secs / 3600) as usize]
+            && self.months[month as usize]
+            && day_matches
+    }
+}
+
+/// Parse a UTC offset as printed by `date +%z`, E.G. `+0930` or `-0500`, into seconds east of UTC.
+pub fn parse_utc_offset(s: &str) -> Option<i32> {
+    let s = s.trim();
+    let (sign, digits) = match s.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01T00:00:00Z, a Monday.
+    const JAN_1_2024: i64 = 1704067200;
+    const DAY: i64 = 86400;
+
+    #[test]
+    fn civil_from_days_converts_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19723), (2024, 1, 1));
+    }
+
+    #[test]
+    fn steps_match_every_nth_value() {
+        let every_15 = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert!(every_15.matches(JAN_1_2024, 0));
+        assert!(every_15.matches(JAN_1_2024 + 45 * 60, 0));
+        assert!(!every_15.matches(JAN_1_2024 + 10 * 60, 0));
+
+        let from_5 = CronSchedule::parse("5/15 * * * *").unwrap();
+        assert!(from_5.matches(JAN_1_2024 + 20 * 60, 0));
+        assert!(!from_5.matches(JAN_1_2024, 0));
+    }
+
+    #[test]
+    fn ranges_are_inclusive() {
+        let schedule = CronSchedule::parse("* 1-4 * * *").unwrap();
+        assert!(!schedule.matches(JAN_1_2024, 0));
+        assert!(schedule.matches(JAN_1_2024 + 3600, 0));
+        assert!(schedule.matches(JAN_1_2024 + 4 * 3600 + 59 * 60, 0));
+        assert!(!schedule.matches(JAN_1_2024 + 5 * 3600, 0));
+    }
+
+    #[test]
+    fn weekdays_count_from_sunday() {
+        let monday = CronSchedule::parse("0 0 * * 1").unwrap();
+        assert!(monday.matches(JAN_1_2024, 0));
+        assert!(!monday.matches(JAN_1_2024 + DAY, 0));
+
+        // 7 is Sunday as well as 0.
+        let sunday = CronSchedule::parse("0 0 * * 7").unwrap();
+        assert!(sunday.matches(JAN_1_2024 + 6 * DAY, 0));
+        assert!(!sunday.matches(JAN_1_2024, 0));
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 13th, or any Friday.
+        let schedule = CronSchedule::parse("0 0 13 * 5").unwrap();
+        assert!(schedule.matches(JAN_1_2024 + 4 * DAY, 0));
+        assert!(schedule.matches(JAN_1_2024 + 12 * DAY, 0));
+        assert!(!schedule.matches(JAN_1_2024 + 5 * DAY, 0));
+
+        // With the day of week left as `*`, only the 13th.
+        let schedule = CronSchedule::parse("0 0 13 * *").unwrap();
+        assert!(!schedule.matches(JAN_1_2024 + 4 * DAY, 0));
+        assert!(schedule.matches(JAN_1_2024 + 12 * DAY, 0));
+    }
+
+    #[test]
+    fn matches_in_host_local_time() {
+        let schedule = CronSchedule::parse("0 9 * * *").unwrap();
+        let offset = parse_utc_offset("+0930").unwrap();
+        // 09:00 at +09:30 is 23:30 UTC the day before.
+        assert!(schedule.matches(JAN_1_2024 + 9 * 3600 - offset as i64, offset));
+        assert!(!schedule.matches(JAN_1_2024 + 9 * 3600, offset));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* 4-1 * * *").is_err());
+        assert!(CronSchedule::parse("* * 0 * *").is_err());
+    }
+
+    #[test]
+    fn parses_utc_offsets() {
+        assert_eq!(parse_utc_offset("+0930"), Some(34200));
+        assert_eq!(parse_utc_offset("-0500\n"), Some(-18000));
+        assert_eq!(parse_utc_offset("+0000"), Some(0));
+        assert_eq!(parse_utc_offset("0930"), None);
+        assert_eq!(parse_utc_offset("+930"), None);
+    }
+}
@@ -0,0 +1,100 @@
+use similar::{ChangeTag, TextDiff};
+
+/// The most bytes of changed text (after trimming the common prefix and suffix) that will be diffed.
+const MAX_DIFF_BYTES: usize = 256 * 1024;
+/// The most hunks shown in a plan description.
+const MAX_HUNKS: usize = 5;
+/// The most lines of diff output shown in a plan description.
+const MAX_LINES: usize = 200;
+
+/// Describe the change from `current` to `desired` as a bounded unified diff, for use in plan output.
+/// Only the region between the longest common prefix and suffix is diffed, and only if it is small,
+/// so that describing a small change to a huge file costs neither much memory nor much time.
+pub fn describe_diff(current: &[u8], desired: &[u8]) -> String {
+    let prefix = common_prefix(current, desired);
+    let max_suffix = current.len().min(desired.len()) - prefix;
+    let suffix = common_suffix(&current[current.len() - max_suffix..], &desired[desired.len() - max_suffix..]);
+
+    let old = &current[prefix..current.len() - suffix];
+    let new = &desired[prefix..desired.len() - suffix];
+
+    if old.len() + new.len() > MAX_DIFF_BYTES {
+        return format!(
+            "({} bytes -> {} bytes, {} bytes differ: too large to diff)",
+            current.len(),
+            desired.len(),
+            old.len().max(new.len())
+        );
+    }
+    let (Ok(old), Ok(new)) = (std::str::from_utf8(old), std::str::from_utf8(new)) else {
+        return format!("({} bytes -> {} bytes, binary)", current.len(), desired.len());
+    };
+
+    // Line numbers in hunk headers are relative to the whole file.
+    let line_offset = current[..prefix].iter().filter(|b| **b == b'\n').count();
+
+    let diff = TextDiff::configure()
+        .timeout(std::time::Duration::from_millis(500))
+        .diff_lines(old, new);
+
+    let mut out = String::new();
+    let mut lines = 0;
+    let hunks: Vec<_> = diff.unified_diff().context_radius(3).iter_hunks().collect();
+    for (i, hunk) in hunks.iter().enumerate() {
+        if i == MAX_HUNKS || lines >= MAX_LINES {
+            out.push_str(&format!("... {} more hunks\n", hunks.len() - i));
+            break;
+        }
+
+        let ops = hunk.ops();
+        let (Some(first), Some(last)) = (ops.first(), ops.last()) else {
+            continue;
+        };
+        let (old_start, old_end) = (first.old_range().start, last.old_range().end);
+        let (new_start, new_end) = (first.new_range().start, last.new_range().end);
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + line_offset + 1,
+            old_end - old_start,
+            new_start + line_offset + 1,
+            new_end - new_start
+        ));
+
+        for change in hunk.iter_changes() {
+            if lines >= MAX_LINES {
+                out.push_str("...\n");
+                break;
+            }
+            let sign = match change.tag() {
+                ChangeTag::Delete => '-',
+                ChangeTag::Insert => '+',
+                ChangeTag::Equal => ' ',
+            };
+            out.push(sign);
+            out.push_str(change.value());
+            if change.missing_newline() {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+            lines += 1;
+        }
+    }
+    out
+}
+
+/// The length of the common prefix of `a` and `b`, backed off to the start of a line.
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    let n = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    match a[..n].iter().rposition(|c| *c == b'\n') {
+        Some(i) => i + 1,
+        None => 0,
+    }
+}
+
+/// The length of the common suffix of `a` and `b`, advanced to the start of a line.
+fn common_suffix(a: &[u8], b: &[u8]) -> usize {
+    let n = a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count();
+    match a[a.len() - n..].iter().position(|c| *c == b'\n') {
+        Some(i) => n - i - 1,
+        None => 0,
+    }
+}
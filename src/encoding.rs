@@ -0,0 +1,40 @@
+use anyhow::bail;
+use encoding_rs::Encoding;
+
+/// Transcode UTF-8 text from the repo into `encoding` for upload.
+/// `encoding` is a WHATWG label such as `latin1` or `shift_jis`, or one of `utf16le`/`utf16be`.
+pub fn encode(text: &[u8], encoding: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let text = std::str::from_utf8(text)?;
+    match encoding.to_lowercase().as_str() {
+        // encoding_rs only decodes UTF-16, so encode it by hand.
+        "utf16le" | "utf-16le" => Ok(text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()),
+        "utf16be" | "utf-16be" => Ok(text.encode_utf16().flat_map(|u| u.to_be_bytes()).collect()),
+        label => {
+            let Some(encoding) = Encoding::for_label(label.as_bytes()) else {
+                bail!("Unknown encoding {}", label);
+            };
+            let (bytes, _, had_errors) = encoding.encode(text);
+            if had_errors {
+                bail!("Text contains characters that can't be represented in {}", encoding.name());
+            }
+            Ok(bytes.into_owned())
+        }
+    }
+}
+
+/// Transcode text read from the remote host in `encoding` into UTF-8 for the repo.
+pub fn decode(bytes: &[u8], encoding: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let label = match encoding.to_lowercase().as_str() {
+        "utf16le" => String::from("utf-16le"),
+        "utf16be" => String::from("utf-16be"),
+        label => label.to_string(),
+    };
+    let Some(encoding) = Encoding::for_label(label.as_bytes()) else {
+        bail!("Unknown encoding {}", label);
+    };
+    let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+    if had_errors {
+        bail!("Remote file is not valid {}", encoding.name());
+    }
+    Ok(text.into_owned().into_bytes())
+}
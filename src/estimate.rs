@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// How much each new observation moves a running average.
+const SMOOTHING: f64 = 0.3;
+/// Ops that transfer fewer bytes than this are timed as fixed overhead rather than as throughput.
+const SMALL_OP_BYTES: u64 = 64 * 1024;
+
+/// Assumed until a host has been timed.
+const DEFAULT_OP_SECS: f64 = 0.5;
+const DEFAULT_BYTES_PER_SEC: f64 = 1024.0 * 1024.0;
+const DEFAULT_HOOK_SECS: f64 = 1.0;
+
+/// Observed op timings for one host, persisted under the prefix so that
+/// plans can estimate how long an apply will take before it is approved.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HostTimings {
+    /// The fixed cost of a file op, in seconds, whatever its size.
+    pub op_secs:       Option<f64>,
+    /// Upload throughput, in bytes per second.
+    pub bytes_per_sec: Option<f64>,
+    /// How long each hook took, in seconds, by its shell command.
+    #[serde(default)]
+    pub hook_secs:     HashMap<String, f64>,
+}
+
+fn smooth(average: Option<f64>, sample: f64) -> f64 {
+    match average {
+        Some(average) => average + SMOOTHING * (sample - average),
+        None => sample,
+    }
+}
+
+/// A rough duration estimate, and whether it is based on timings observed on the host.
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+    pub secs:         f64,
+    pub from_history: bool,
+}
+
+impl HostTimings {
+    /// Record that a file op transferring `bytes` took `secs`.
+    pub fn record_op(&mut self, bytes: u64, secs: f64) {
+        if bytes < SMALL_OP_BYTES {
+            self.op_secs = Some(smooth(self.op_secs, secs));
+        } else {
+            let transfer_secs = (secs - self.op_secs.unwrap_or(0.0)).max(0.001);
+            self.bytes_per_sec = Some(smooth(self.bytes_per_sec, bytes as f64 / transfer_secs));
+        }
+    }
+
+    pub fn record_hook(&mut self, shell: &str, secs: f64) {
+        let average = self.hook_secs.get(shell).copied();
+        self.hook_secs.insert(shell.to_string(), smooth(average, secs));
+    }
+
+    /// Estimate a file op transferring `bytes`.
+    pub fn estimate_op(&self, bytes: u64) -> Estimate {
+        let secs = self.op_secs.unwrap_or(DEFAULT_OP_SECS)
+            + bytes as f64 / self.bytes_per_sec.unwrap_or(DEFAULT_BYTES_PER_SEC);
+        let from_history = self.op_secs.is_some() && (bytes < SMALL_OP_BYTES || self.bytes_per_sec.is_some());
+        Estimate { secs, from_history }
+    }
+
+    pub fn estimate_hook(&self, shell: &str) -> Estimate {
+        match self.hook_secs.get(shell) {
+            Some(secs) => Estimate {
+                secs: *secs,
+                from_history: true,
+            },
+            None => Estimate {
+                secs: DEFAULT_HOOK_SECS,
+                from_history: false,
+            },
+        }
+    }
+}
+
+/// Render a rough duration, E.G. `~2m10s`.
+pub fn format_secs(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs < 1 {
+        String::from("<1s")
+    } else if secs < 60 {
+        format!("~{}s", secs)
+    } else if secs < 3600 {
+        format!("~{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("~{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+impl Estimate {
+    /// Render as a plan annotation, E.G. `est. ~2m10s` or `est. ~1s, untimed`.
+    pub fn describe(&self) -> String {
+        if self.from_history {
+            format!("est. {}", format_secs(self.secs))
+        } else {
+            format!("est. {}, untimed", format_secs(self.secs))
+        }
+    }
+}
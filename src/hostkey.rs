@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use anyhow::{Context, bail};
+use base64::{Engine, engine::general_purpose::STANDARD_NO_PAD};
+use ssh2::{CheckResult, HashType, KnownHostFileKind, Session};
+
+/// How a server's host key compares with what a known_hosts file records for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnownHostCheck {
+    Match,
+    /// The file has no key for the host.
+    NotFound,
+    /// The file has a different key for the host.
+    Mismatch,
+}
+
+/// Return the SHA256 fingerprint of the host key of an established `session` in OpenSSH format,
+/// E.G. `SHA256:uNiVztksCsDhcc0u9e8BujQXVUpKZIDTMczCvj3tD2s`.
+pub fn host_key_fingerprint(session: &Session) -> Result<String, anyhow::Error> {
+    let Some(hash) = session.host_key_hash(HashType::Sha256) else {
+        bail!("Server did not present a host key");
+    };
+
+    Ok(format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+}
+
+/// Return the fingerprint of the host key of an established `session`, as host_key_fingerprint does,
+/// and check the key against the entries for `host:port` in the OpenSSH known_hosts file at `known_hosts`.
+/// A missing file has no entries.
+pub fn check_host_key(
+    session: &Session,
+    known_hosts: &Path,
+    host: &str,
+    port: u16,
+) -> Result<(String, KnownHostCheck), anyhow::Error> {
+    let fingerprint = host_key_fingerprint(session)?;
+    let Some((key, _)) = session.host_key() else {
+        bail!("Server did not present a host key");
+    };
+
+    if !known_hosts.exists() {
+        return Ok((fingerprint, KnownHostCheck::NotFound));
+    }
+    let mut entries = session.known_hosts()?;
+    entries
+        .read_file(known_hosts, KnownHostFileKind::OpenSSH)
+        .with_context(|| format!("Reading known_hosts file {}", known_hosts.display()))?;
+    let check = match entries.check_port(host, port, key) {
+        CheckResult::Match => KnownHostCheck::Match,
+        CheckResult::NotFound => KnownHostCheck::NotFound,
+        CheckResult::Mismatch => KnownHostCheck::Mismatch,
+        CheckResult::Failure => bail!("Failed to check the host key of {} against {}", host, known_hosts.display()),
+    };
+    Ok((fingerprint, check))
+}
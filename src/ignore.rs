@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use glob_match::glob_match;
+
+use crate::addr::RemoteFsPath;
+
+/// A file of gitignore-style patterns in a host's local tree, E.G. `remotefs/web-01/var/www/.remotefsignore`,
+/// naming files under its directory that list and filter should pass over, as if they weren't on the host.
+/// Put one at `remotefs/<host>/.remotefsignore` to apply it to the whole host, or in a mount's directory
+/// to apply it to the mount. As with .gitignore, deeper files take precedence, a later pattern overrides an
+/// earlier one, and a file can't be re-included with `!` once a directory above it is ignored.
+pub const IGNORE_FILE_NAME: &str = ".remotefsignore";
+
+/// One pattern of an ignore file.
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    glob:     String,
+    /// Set for `!pattern`, which re-includes what an earlier pattern ignored.
+    negate:   bool,
+    /// Set for `pattern/`, which matches only directories.
+    dir_only: bool,
+    /// Set if the pattern contains a `/` other than a trailing one, and so matches a path
+    /// relative to the ignore file's directory, rather than a file name at any depth.
+    anchored: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreFile {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreFile {
+    pub fn parse(body: &str) -> Self {
+        let mut rules = Vec::new();
+        for line in body.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negate, line) = match line.strip_prefix('!') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            // `\#` and `\!` escape a leading `#` or `!`.
+            let line = line.strip_prefix('\\').filter(|l| l.starts_with(['#', '!'])).unwrap_or(line);
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let glob = line.trim_start_matches('/');
+            if glob.is_empty() {
+                continue;
+            }
+            rules.push(IgnoreRule {
+                glob: glob.to_string(),
+                negate,
+                dir_only,
+                anchored,
+            });
+        }
+        IgnoreFile { rules }
+    }
+
+    /// Whether `path`, relative to this file's directory, is ignored by it,
+    /// or None if none of its patterns match.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let path = path.to_string_lossy();
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| is_dir || !rule.dir_only)
+            .find(|rule| {
+                if rule.anchored {
+                    glob_match(&rule.glob, &path)
+                } else {
+                    glob_match(&rule.glob, name)
+                }
+            })
+            .map(|rule| !rule.negate)
+    }
+}
+
+/// The ignore files of one host's local tree, read as they are needed.
+pub struct HostIgnores {
+    hostname: String,
+    root:     PathBuf,
+    files:    HashMap<PathBuf, Option<IgnoreFile>>,
+}
+
+impl HostIgnores {
+    pub fn new(prefix: &Path, hostname: &str) -> Self {
+        HostIgnores {
+            hostname: hostname.to_string(),
+            root:     prefix.join("remotefs").join(hostname),
+            files:    HashMap::new(),
+        }
+    }
+
+    /// Whether `path`, relative to the host's local tree as in RemoteFsPath::path, is ignored,
+    /// either itself or by way of a directory above it.
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let components: Vec<_> = path.components().collect();
+        (1..=components.len()).any(|i| {
+            let sub: PathBuf = components[..i].iter().collect();
+            self.matched(&sub, is_dir || i < components.len())
+        })
+    }
+
+    /// Whether the file or directory at `remote_path` on the host is ignored.
+    pub fn is_ignored_remote(&mut self, remote_path: &Path, is_dir: bool) -> bool {
+        let addr = RemoteFsPath::from_remote_path(&self.hostname, remote_path);
+        self.is_ignored(&addr.path, is_dir)
+    }
+
+    /// Whether `path` itself is ignored by the ignore files in the directories above it.
+    fn matched(&mut self, path: &Path, is_dir: bool) -> bool {
+        let mut dirs: Vec<&Path> = path.ancestors().skip(1).collect();
+        dirs.reverse();
+        let mut ignored = false;
+        for dir in dirs {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let file = self.files.entry(dir.to_path_buf()).or_insert_with(|| {
+                std::fs::read_to_string(self.root.join(dir).join(IGNORE_FILE_NAME))
+                    .ok()
+                    .map(|body| IgnoreFile::parse(&body))
+            });
+            if let Some(matched) = file.as_ref().and_then(|file| file.matched(relative, is_dir)) {
+                ignored = matched;
+            }
+        }
+        ignored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_patterns_override_earlier() {
+        let file = IgnoreFile::parse("# logs\n*.log\n!keep.log\n");
+        assert_eq!(file.matched(Path::new("var/app.log"), false), Some(true));
+        assert_eq!(file.matched(Path::new("keep.log"), false), Some(false));
+        assert_eq!(file.matched(Path::new("index.html"), false), None);
+    }
+
+    #[test]
+    fn trailing_slash_matches_only_directories() {
+        let file = IgnoreFile::parse("cache/\n");
+        assert_eq!(file.matched(Path::new("cache"), true), Some(true));
+        assert_eq!(file.matched(Path::new("cache"), false), None);
+    }
+
+    #[test]
+    fn patterns_with_a_slash_are_anchored() {
+        let file = IgnoreFile::parse("/build\nsrc/*.tmp\n");
+        assert_eq!(file.matched(Path::new("build"), true), Some(true));
+        assert_eq!(file.matched(Path::new("src/build"), true), None);
+        assert_eq!(file.matched(Path::new("src/a.tmp"), false), Some(true));
+        assert_eq!(file.matched(Path::new("lib/src/a.tmp"), false), None);
+    }
+
+    #[test]
+    fn escaped_leading_hash_is_literal() {
+        let file = IgnoreFile::parse("\\#notes\n");
+        assert_eq!(file.matched(Path::new("#notes"), false), Some(true));
+    }
+
+    #[test]
+    fn deeper_files_re_include_but_not_under_ignored_directories() {
+        let prefix = tempfile::tempdir().unwrap();
+        let root = prefix.path().join("remotefs/web-01");
+        std::fs::create_dir_all(root.join("var")).unwrap();
+        std::fs::write(root.join(IGNORE_FILE_NAME), "cache/\n*.log\n!keep.log\n").unwrap();
+        std::fs::write(root.join("var").join(IGNORE_FILE_NAME), "!debug.log\n").unwrap();
+
+        let mut ignores = HostIgnores::new(prefix.path(), "web-01");
+        assert!(ignores.is_ignored(Path::new("var/app.log"), false));
+        assert!(!ignores.is_ignored(Path::new("var/debug.log"), false));
+        assert!(!ignores.is_ignored(Path::new("keep.log"), false));
+        assert!(ignores.is_ignored(Path::new("cache/keep.log"), false));
+        assert!(!ignores.is_ignored(Path::new("var/index.html"), false));
+    }
+}
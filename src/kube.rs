@@ -0,0 +1,240 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
+
+use remotefs::{
+    File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult,
+    fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream},
+};
+
+use crate::{
+    config::RemoteFsKube,
+    shell::{self, ShellFs, protocol_error},
+    util::shell_quote_path,
+};
+
+/// A RemoteFs over a container in a Kubernetes pod. Every operation is a shell command run with
+/// `kubectl exec`, and file contents are streamed through its stdin and stdout, much as `kubectl cp` does.
+pub struct KubeFs {
+    target:    RemoteFsKube,
+    connected: bool,
+    wd:        PathBuf,
+    /// The kubectl process behind the stream last returned by open, create or append,
+    /// with the metadata to apply to a created file once it has been written.
+    pending:   Option<(Child, Option<(PathBuf, Metadata)>)>,
+}
+
+impl KubeFs {
+    pub fn new(target: RemoteFsKube) -> Self {
+        KubeFs {
+            target,
+            connected: false,
+            wd: PathBuf::from("/"),
+            pending: None,
+        }
+    }
+
+    /// A `kubectl exec` command that runs `script` under sh in the container.
+    fn command(&self, script: &str) -> Command {
+        let mut cmd = Command::new(self.target.kubectl.as_deref().unwrap_or(Path::new("kubectl")));
+        if let Some(kubeconfig) = &self.target.kubeconfig {
+            cmd.arg("--kubeconfig").arg(kubeconfig);
+        }
+        if let Some(context) = &self.target.context {
+            cmd.arg("--context").arg(context);
+        }
+        cmd.args(["exec", "-i", "-n", &self.target.namespace, &self.target.pod]);
+        if let Some(container) = &self.target.container {
+            cmd.args(["-c", container]);
+        }
+        cmd.args(["--", "sh", "-c", script]);
+        cmd
+    }
+
+    /// Run `script` in the container, returning its exit code and stdout.
+    fn run(&self, script: &str) -> RemoteResult<(u32, String)> {
+        let output = self
+            .command(script)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| RemoteError::new_ex(RemoteErrorType::ConnectionError, e))?;
+        // kubectl exits with the command's own exit code, or with 1 and no stdout when it fails itself.
+        let Some(code) = output.status.code() else {
+            return Err(protocol_error("kubectl was killed by a signal"));
+        };
+        Ok((code as u32, String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+
+    fn absolute(&self, path: &Path) -> PathBuf {
+        self.wd.join(path)
+    }
+
+    /// Start `script` with its stdin or stdout attached to a stream, to be finished by on_written or on_read.
+    fn spawn(&mut self, script: &str, write: bool) -> RemoteResult<Child> {
+        if let Some((mut child, _)) = self.pending.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        let mut cmd = self.command(script);
+        if write {
+            cmd.stdin(Stdio::piped()).stdout(Stdio::null());
+        } else {
+            cmd.stdin(Stdio::null()).stdout(Stdio::piped());
+        }
+        cmd.stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| RemoteError::new_ex(RemoteErrorType::ConnectionError, e))
+    }
+
+    fn write_stream(&mut self, path: &Path, metadata: &Metadata, append: bool) -> RemoteResult<WriteStream> {
+        let path = self.absolute(path);
+        let redirect = if append { ">>" } else { ">" };
+        let mut child = self.spawn(&format!("cat {} {}", redirect, shell_quote_path(&path)), true)?;
+        let stdin = child.stdin.take().ok_or_else(|| protocol_error("kubectl has no stdin"))?;
+        self.pending = Some((child, Some((path, metadata.clone()))));
+        Ok(WriteStream::from(Box::new(stdin) as Box<dyn Write + Send>))
+    }
+
+    /// Wait for the kubectl process behind the last stream to exit.
+    fn finish_pending(&mut self, error: RemoteErrorType) -> RemoteResult<Option<(PathBuf, Metadata)>> {
+        let Some((child, metadata)) = self.pending.take() else {
+            return Ok(None);
+        };
+        let output = child
+            .wait_with_output()
+            .map_err(|e| RemoteError::new_ex(RemoteErrorType::IoError, e))?;
+        if !output.status.success() {
+            return Err(RemoteError::new_ex(error, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(metadata)
+    }
+}
+
+impl ShellFs for KubeFs {
+    fn run_checked(&self, script: &str, error: RemoteErrorType) -> RemoteResult<String> {
+        let output = self
+            .command(script)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| RemoteError::new_ex(RemoteErrorType::ConnectionError, e))?;
+        if !output.status.success() {
+            return Err(RemoteError::new_ex(
+                error,
+                format!("`{}`: {}", script, String::from_utf8_lossy(&output.stderr).trim()),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl RemoteFs for KubeFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let wd = self.run_checked("pwd", RemoteErrorType::ConnectionError)?;
+        self.wd = PathBuf::from(wd.trim());
+        self.connected = true;
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        if let Some((mut child, _)) = self.pending.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.connected = false;
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wd.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.wd = shell::change_dir(self, &self.absolute(dir))?;
+        Ok(self.wd.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        shell::list_dir(self, &self.absolute(path))
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        shell::stat(self, &self.absolute(path))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        shell::setstat(self, &self.absolute(path), metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        shell::exists(self, &self.absolute(path))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        shell::remove_file(self, &self.absolute(path))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        shell::remove_dir(self, &self.absolute(path))
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        shell::remove_dir_all(self, &self.absolute(path))
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        shell::create_dir(self, &self.absolute(path), mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        shell::symlink(self, &self.absolute(path), target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        shell::copy(self, &self.absolute(src), &self.absolute(dest))
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        shell::mov(self, &self.absolute(src), &self.absolute(dest))
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        self.run(&format!("cd {} && {}", shell_quote_path(&self.wd), cmd))
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.write_stream(path, metadata, true)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.write_stream(path, metadata, false)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let path_q = shell_quote_path(&self.absolute(path));
+        let mut child = self.spawn(&format!("cat -- {}", path_q), false)?;
+        let stdout = child.stdout.take().ok_or_else(|| protocol_error("kubectl has no stdout"))?;
+        self.pending = Some((child, None));
+        Ok(ReadStream::from(Box::new(stdout) as Box<dyn Read + Send>))
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        drop(readable);
+        self.finish_pending(RemoteErrorType::CouldNotOpenFile)?;
+        Ok(())
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        // Closing kubectl's stdin ends the upload.
+        drop(writable);
+        if let Some((path, metadata)) = self.finish_pending(RemoteErrorType::FileCreateDenied)? {
+            self.setstat(&path, metadata)?;
+        }
+        Ok(())
+    }
+}
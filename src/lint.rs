@@ -0,0 +1,155 @@
+use crate::config::{RemoteFsConfig, RemoteFsHook};
+
+/// Words that open a compound command, and the word that must close it.
+const COMPOUNDS: &[(&str, &str)] = &[("if", "fi"), ("case", "esac"), ("do", "done")];
+
+/// Check a hook's shell command for mistakes that sh would only report once the hook runs:
+/// unbalanced quotes and brackets, dangling operators, and unclosed compound commands.
+/// This is no substitute for shellcheck, but catches the typos that most often break a hook mid-apply.
+pub fn lint_shell(shell: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    // The stack of open quotes and brackets, as the character that closes each.
+    let mut open: Vec<char> = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut chars = shell.chars().peekable();
+    while let Some(c) = chars.next() {
+        let quoted = matches!(open.last(), Some('\'' | '"'));
+        match c {
+            '\'' if open.last() == Some(&'\'') => {
+                open.pop();
+            }
+            _ if open.last() == Some(&'\'') => {}
+            '\\' => {
+                chars.next();
+            }
+            '"' if open.last() == Some(&'"') => {
+                open.pop();
+            }
+            '\'' if !quoted => open.push('\''),
+            '"' => open.push('"'),
+            '`' if open.last() == Some(&'`') => {
+                open.pop();
+            }
+            '`' => open.push('`'),
+            '$' if chars.peek() == Some(&'(') => {
+                chars.next();
+                open.push(')');
+            }
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                open.push('}');
+            }
+            '(' if !quoted => open.push(')'),
+            ')' | '}' if open.last() == Some(&c) => {
+                open.pop();
+            }
+            // Patterns in a case statement end with a bare `)`.
+            ')' if !quoted && !words.iter().any(|w| w == "case") => problems.push(String::from("Unmatched `)`")),
+            _ => {}
+        }
+
+        if !quoted && (c.is_whitespace() || matches!(c, ';' | '&' | '|' | '(' | ')')) {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            if !c.is_whitespace() {
+                words.push(c.to_string());
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+
+    for close in open.iter().rev() {
+        problems.push(match close {
+            '\'' => String::from("Unterminated single quote"),
+            '"' => String::from("Unterminated double quote"),
+            '`' => String::from("Unterminated backtick command substitution"),
+            ')' => String::from("Unclosed `(` or `$(`"),
+            _ => String::from("Unclosed `${`"),
+        });
+    }
+
+    let trimmed = shell.trim_end();
+    if open.is_empty() && (trimmed.ends_with("&&") || trimmed.ends_with('|') && !trimmed.ends_with("\\|")) {
+        problems.push(String::from("Command ends with a dangling `|`, `&&` or `||`"));
+    }
+    if let Some(first) = words.first()
+        && matches!(first.as_str(), "|" | "&" | ";")
+    {
+        problems.push(format!("Command starts with `{}`", first));
+    }
+
+    for (opener, closer) in COMPOUNDS {
+        let opened = words.iter().filter(|w| w == opener).count();
+        let closed = words.iter().filter(|w| w == closer).count();
+        if opened > closed {
+            problems.push(format!("`{}` without a matching `{}`", opener, closer));
+        } else if closed > opened {
+            problems.push(format!("`{}` without a matching `{}`", closer, opener));
+        }
+    }
+
+    // `rm -r $DIR/...` removes from `/` if DIR is unset or empty.
+    for (i, w) in words.iter().enumerate() {
+        if w != "rm" {
+            continue;
+        }
+        let args: Vec<&String> = words[i + 1..]
+            .iter()
+            .take_while(|w| !matches!(w.as_str(), ";" | "&" | "|"))
+            .collect();
+        let recursive = args.iter().any(|arg| {
+            *arg == "--recursive" || arg.starts_with('-') && !arg.starts_with("--") && arg.contains(['r', 'R'])
+        });
+        let unguarded = args.iter().any(|arg| {
+            let arg = arg.trim_start_matches('"');
+            arg.starts_with('$') && arg.contains('/') && !arg.contains(":?")
+        });
+        if recursive && unguarded {
+            problems.push(String::from(
+                "`rm -r $VAR/...` removes from `/` if VAR is empty. Use `${VAR:?}/...` instead",
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Lint every hook and capture command in `config`, returning each problem with the command it was found in.
+pub fn lint_config(config: &RemoteFsConfig) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    let profiles = config.profiles.iter().flat_map(|profiles| profiles.values());
+    for mount in config.hosts.values().map(|host| &host.mounts).chain(profiles).flatten() {
+        let hooks = mount
+            .pre_hooks
+            .iter()
+            .chain(mount.post_hooks.iter())
+            .chain(mount.on_delete_hooks.iter())
+            .flatten();
+        for hook in hooks {
+            problems.extend(lint_hook(hook).into_iter().map(|problem| (hook.shell.clone(), problem)));
+        }
+    }
+    for host in config.hosts.values() {
+        for capture in host.captures.iter().flatten() {
+            problems.extend(lint_shell(&capture.shell).into_iter().map(|problem| (capture.shell.clone(), problem)));
+        }
+    }
+    problems
+}
+
+fn lint_hook(hook: &RemoteFsHook) -> Vec<String> {
+    let mut problems = lint_shell(&hook.shell);
+    if hook.shell.contains("${old_content}") && !hook.stage_old_content {
+        problems.push(String::from(
+            "`${old_content}` is only substituted with `stage_old_content: true`",
+        ));
+    }
+    problems
+}
@@ -1,14 +1,49 @@
-use autoschematic_core::tarpc_bridge::tarpc_connector_main;
+use autoschematic_core::{tarpc_bridge::tarpc_connector_main, util::RON};
 use connector::RemoteFsConnector;
 
 pub mod connector;
 pub mod config;
 pub mod addr;
 pub mod resource;
+pub mod archive;
+pub mod util;
+pub mod hostkey;
+pub mod proxy;
+pub mod sshconfig;
+pub mod encoding;
+pub mod diff;
+pub mod secret;
+pub mod checksum;
+pub mod estimate;
+pub mod manifest;
+pub mod kube;
+pub mod lint;
+pub mod cron;
+pub mod cert;
+pub mod prompt;
+pub mod ignore;
+pub mod migrate;
+pub mod sftp;
+pub mod shell;
+pub mod scp;
 
 
 #[tokio::main]
 pub async fn main() -> anyhow::Result<()> {
+    if std::env::args().any(|arg| arg == "--config-schema") {
+        println!("{}", config::config_schema()?);
+        return Ok(());
+    }
+
+    // `--migrate-config <path>` prints the config at `path` rewritten to share repeated mounts as profiles.
+    if let Some(path) = std::env::args().skip_while(|arg| arg != "--migrate-config").nth(1) {
+        let body = std::fs::read_to_string(&path)?;
+        let config: config::RemoteFsConfig = RON.from_str(&body)?;
+        let config = migrate::migrate_config(config)?;
+        println!("{}", ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default())?);
+        return Ok(());
+    }
+
     tarpc_connector_main::<RemoteFsConnector>().await?;
     Ok(())
 }
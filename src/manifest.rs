@@ -0,0 +1,21 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::RemoteFsChecksum;
+
+/// A mount's manifest, as stored on the host: the sha256 of the repo copy of
+/// every file the connector last wrote to the mount, by absolute remote path.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: BTreeMap<PathBuf, String>,
+}
+
+/// The local copy of a host's manifest, kept under the prefix along with the remote checksum
+/// of the manifest file it mirrors, so that an unchanged manifest is never downloaded twice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalManifest {
+    pub checksum: RemoteFsChecksum,
+    pub digest:   String,
+    pub manifest: Manifest,
+}
@@ -0,0 +1,84 @@
+use crate::config::{RemoteFsConfig, RemoteFsGroup};
+
+/// Rewrite `config` so that each list of mounts repeated across hosts is declared once, as a profile that
+/// those hosts name in place of their own mounts. Each such set of hosts is also made a group, named after
+/// the profile, unless a group of exactly those hosts exists already, so that files they share can be
+/// kept once under `remotefs/@<group>/`. Every host keeps the same mounts, in the same order, as before.
+/// Hosts that already name profiles are left as they are.
+pub fn migrate_config(mut config: RemoteFsConfig) -> Result<RemoteFsConfig, anyhow::Error> {
+    let mut hostnames: Vec<String> = config.hosts.keys().cloned().collect();
+    hostnames.sort();
+
+    // Mounts don't implement PartialEq, so mount lists are compared by their serialized form.
+    let mut shared: Vec<(serde_json::Value, Vec<String>)> = Vec::new();
+    for hostname in hostnames {
+        let host = &config.hosts[&hostname];
+        if host.profiles.is_some() || host.mounts.is_empty() {
+            continue;
+        }
+        let mounts = serde_json::to_value(&host.mounts)?;
+        match shared.iter_mut().find(|(other, _)| *other == mounts) {
+            Some((_, members)) => members.push(hostname),
+            None => shared.push((mounts, vec![hostname])),
+        }
+    }
+
+    let mut profiles = config.profiles.take().unwrap_or_default();
+    let mut groups = config.groups.take().unwrap_or_default();
+    for (_, members) in shared.into_iter().filter(|(_, members)| members.len() > 1) {
+        let name = profile_name(&members, |name| profiles.contains_key(name) || groups.contains_key(name));
+
+        let mut mounts = Vec::new();
+        for member in &members {
+            let Some(host) = config.hosts.get_mut(member) else {
+                continue;
+            };
+            mounts = std::mem::take(&mut host.mounts);
+            host.profiles = Some(vec![name.clone()]);
+        }
+        profiles.insert(name.clone(), mounts);
+
+        let grouped = groups.values().any(|group| {
+            group.hosts.len() == members.len() && members.iter().all(|member| group.hosts.contains(member))
+        });
+        if !grouped {
+            groups.insert(
+                name,
+                RemoteFsGroup {
+                    hosts: members,
+                    failure_policy: None,
+                    replicas: false,
+                },
+            );
+        }
+    }
+
+    config.profiles = (!profiles.is_empty()).then_some(profiles);
+    config.groups = (!groups.is_empty()).then_some(groups);
+    Ok(config)
+}
+
+/// A name for the profile of `hosts`: the stem their hostnames share, E.G. `web` for `web-01` and `web-02`,
+/// or else `profile`, suffixed with a number if need be so that it isn't `taken`.
+fn profile_name(hosts: &[String], taken: impl Fn(&str) -> bool) -> String {
+    let first: Vec<char> = hosts[0].chars().collect();
+    let common = hosts
+        .iter()
+        .map(|host| first.iter().zip(host.chars()).take_while(|(a, b)| **a == *b).count())
+        .min()
+        .unwrap_or(0);
+    let stem: String = first[..common]
+        .iter()
+        .map(|&c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    let stem = stem.trim_end_matches(|c: char| !c.is_ascii_alphabetic());
+    let stem = if stem.is_empty() { "profile" } else { stem };
+
+    let mut name = stem.to_string();
+    let mut n = 2;
+    while taken(&name) {
+        name = format!("{}-{}", stem, n);
+        n += 1;
+    }
+    name
+}
@@ -0,0 +1,143 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, bail};
+use regex::Regex;
+use ssh2::{KeyboardInteractivePrompt, Prompt};
+
+use crate::{config::RemoteFsKeyboardInteractive, secret};
+
+/// Sends a message to the user, E.G. through the connector's outbox.
+pub type Notify = Arc<dyn Fn(String) + Send + Sync>;
+
+/// Where the answer to a keyboard-interactive prompt comes from.
+#[derive(Debug, Clone)]
+pub enum Answer {
+    /// A fixed answer, already resolved from its secret reference.
+    Fixed(String),
+    /// Ask the user through the outbox, and wait for them to write the answer to a file.
+    Relay,
+}
+
+/// Answers keyboard-interactive prompts (E.G. a bastion asking for a one-time password)
+/// by matching each prompt against a list of regexes.
+#[derive(Clone)]
+pub struct PromptAnswers {
+    pub hostname:    String,
+    pub answers:     Vec<(Regex, Answer)>,
+    /// Where a relayed answer is expected. It is removed once read.
+    pub answer_path: PathBuf,
+    pub timeout:     Duration,
+    pub notify:      Notify,
+    /// The first prompt that could not be answered, reported if authentication then fails.
+    pub error:       Option<String>,
+}
+
+impl fmt::Debug for PromptAnswers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Answers are secrets.
+        f.debug_struct("PromptAnswers")
+            .field("hostname", &self.hostname)
+            .field("answer_path", &self.answer_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PromptAnswers {
+    fn answer(&self, prompt: &str) -> Result<String, anyhow::Error> {
+        let Some((_, answer)) = self.answers.iter().find(|(regex, _)| regex.is_match(prompt)) else {
+            bail!("No answer configured for prompt {:?} from {}", prompt, self.hostname);
+        };
+        match answer {
+            Answer::Fixed(answer) => Ok(answer.clone()),
+            Answer::Relay => self.relay(prompt),
+        }
+    }
+
+    /// Relay `prompt` to the user, and wait for them to write the answer to answer_path.
+    fn relay(&self, prompt: &str) -> Result<String, anyhow::Error> {
+        relay(
+            &self.notify,
+            &format!("{} asks: {:?}.", self.hostname, prompt.trim()),
+            &self.answer_path,
+            self.timeout,
+        )
+        .with_context(|| format!("Answering {:?} from {}", prompt.trim(), self.hostname))
+    }
+
+    /// Build the answers for `hostname` from its config, resolving each fixed answer's secret reference.
+    pub fn from_config(
+        hostname: &str,
+        config: &RemoteFsKeyboardInteractive,
+        prefix: &Path,
+        notify: Notify,
+    ) -> Result<Self, anyhow::Error> {
+        let mut answers = Vec::new();
+        for answer in &config.answers {
+            let regex = Regex::new(&answer.prompt)
+                .with_context(|| format!("Invalid prompt regex {:?} for {}", answer.prompt, hostname))?;
+            let resolved = match &answer.answer {
+                Some(value) => Answer::Fixed(
+                    secret::resolve(value, prefix)
+                        .with_context(|| format!("Resolving the answer to {:?} for {}", answer.prompt, hostname))?,
+                ),
+                None => Answer::Relay,
+            };
+            answers.push((regex, resolved));
+        }
+        Ok(PromptAnswers {
+            hostname: hostname.to_string(),
+            answers,
+            answer_path: prefix.join("remotefs/.state/prompts").join(format!("{}.answer", hostname)),
+            timeout: Duration::from_secs(config.relay_timeout_secs.unwrap_or(120)),
+            notify,
+            error: None,
+        })
+    }
+}
+
+/// Ask the user `question`, and wait for them to write the answer to `answer_path`, which is removed once read.
+pub fn relay(notify: &Notify, question: &str, answer_path: &Path, timeout: Duration) -> Result<String, anyhow::Error> {
+    // A stale answer would be replayed to a fresh prompt.
+    let _ = std::fs::remove_file(answer_path);
+    if let Some(parent) = answer_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    notify(format!(
+        "{} Write the answer to {} within {}s",
+        question,
+        answer_path.display(),
+        timeout.as_secs()
+    ));
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Ok(body) = std::fs::read_to_string(answer_path) {
+            let _ = std::fs::remove_file(answer_path);
+            return Ok(body.trim_end_matches(['\r', '\n']).to_string());
+        }
+        if Instant::now() >= deadline {
+            bail!("Timed out waiting for an answer");
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+impl KeyboardInteractivePrompt for PromptAnswers {
+    fn prompt<'a>(&mut self, _username: &str, _instructions: &str, prompts: &[Prompt<'a>]) -> Vec<String> {
+        prompts
+            .iter()
+            .map(|prompt| match self.answer(&prompt.text) {
+                Ok(answer) => answer,
+                Err(e) => {
+                    self.error.get_or_insert_with(|| format!("{:#}", e));
+                    String::new()
+                }
+            })
+            .collect()
+    }
+}
@@ -1,20 +1,425 @@
+use anyhow::bail;
 use autoschematic_core::connector::{Resource, ResourceAddress};
 use serde::{Deserialize, Serialize};
 
+use crate::config::{RemoteFsChecksum, RemoteFsHook};
+
+/// The marker that begins a metadata header line, E.G.:
+/// `#% remotefs: mode=0644 uid=0 gid=0`
+pub const HEADER_MARKER: &str = "#% remotefs:";
+
+/// A FileHeader records the ownership and permissions of a remote file
+/// in a single line above its contents, so that they show up in review.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHeader {
+    pub mode: Option<u32>,
+    pub uid:  Option<u32>,
+    pub gid:  Option<u32>,
+}
+
+impl FileHeader {
+    pub fn render(&self) -> String {
+        let mut line = String::from(HEADER_MARKER);
+        if let Some(mode) = self.mode {
+            line.push_str(&format!(" mode={:04o}", mode & 0o7777));
+        }
+        if let Some(uid) = self.uid {
+            line.push_str(&format!(" uid={}", uid));
+        }
+        if let Some(gid) = self.gid {
+            line.push_str(&format!(" gid={}", gid));
+        }
+        line.push('\n');
+        line
+    }
+
+    /// The fields of `desired` that are set and differ from `self`.
+    pub fn changes_to(&self, desired: &FileHeader) -> FileHeader {
+        FileHeader {
+            mode: desired.mode.filter(|m| Some(*m) != self.mode),
+            uid:  desired.uid.filter(|u| Some(*u) != self.uid),
+            gid:  desired.gid.filter(|g| Some(*g) != self.gid),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mode.is_none() && self.uid.is_none() && self.gid.is_none()
+    }
+
+    /// Describe the transition from `self` to `desired` as E.G. `chmod 0644 -> 0600, chown 0 -> 1000`.
+    pub fn describe_changes(&self, desired: &FileHeader) -> String {
+        fn show(v: Option<u32>, octal: bool) -> String {
+            match v {
+                Some(v) if octal => format!("{:04o}", v & 0o7777),
+                Some(v) => v.to_string(),
+                None => String::from("?"),
+            }
+        }
+
+        let changes = self.changes_to(desired);
+        let mut parts = Vec::new();
+        if changes.mode.is_some() {
+            parts.push(format!("chmod {} -> {}", show(self.mode, true), show(desired.mode, true)));
+        }
+        if changes.uid.is_some() {
+            parts.push(format!("chown {} -> {}", show(self.uid, false), show(desired.uid, false)));
+        }
+        if changes.gid.is_some() {
+            parts.push(format!("chgrp {} -> {}", show(self.gid, false), show(desired.gid, false)));
+        }
+        parts.join(", ")
+    }
+
+    pub fn parse(line: &str) -> Result<Self, anyhow::Error> {
+        let Some(fields) = line.trim_end().strip_prefix(HEADER_MARKER) else {
+            bail!("Not a remotefs header: {}", line);
+        };
+
+        let mut header = FileHeader::default();
+        for field in fields.split_whitespace() {
+            match field.split_once('=') {
+                Some(("mode", v)) => header.mode = Some(u32::from_str_radix(v.trim_start_matches("0o"), 8)?),
+                Some(("uid", v)) => header.uid = Some(v.parse()?),
+                Some(("gid", v)) => header.gid = Some(v.parse()?),
+                _ => bail!("Invalid field in remotefs header: {}", field),
+            }
+        }
+        Ok(header)
+    }
+}
+
+/// The line that opens a front-matter block at the top of a file in the repo.
+/// The block runs to the next line consisting of `---`, E.G.:
+/// ```text
+/// --- remotefs
+/// mode: 0o640
+/// uid: 0
+/// validate: "nginx -t -c ${path}"
+/// ---
+/// ```
+pub const FRONT_MATTER_OPEN: &str = "--- remotefs\n";
+pub const FRONT_MATTER_CLOSE: &str = "---\n";
+
+/// Per-file options declared in YAML front matter, as an alternative to a sidecar for teams
+/// who prefer self-describing files. Front matter only exists in the repo: it is stripped before upload.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrontMatter {
+    pub mode:       Option<u32>,
+    pub uid:        Option<u32>,
+    pub gid:        Option<u32>,
+    pub validate:   Option<String>,
+    pub pre_hooks:  Option<Vec<RemoteFsHook>>,
+    pub post_hooks: Option<Vec<RemoteFsHook>>,
+    pub skip_hooks: Option<Vec<String>>,
+}
+
+/// Split a leading front-matter block (if any) off of the raw file body, returning its YAML and the rest.
+fn split_front_matter(s: &[u8]) -> Result<(Option<String>, &[u8]), anyhow::Error> {
+    let Some(rest) = s.strip_prefix(FRONT_MATTER_OPEN.as_bytes()) else {
+        return Ok((None, s));
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive(|b| *b == b'\n') {
+        if line == FRONT_MATTER_CLOSE.as_bytes() || line == FRONT_MATTER_CLOSE.trim_end().as_bytes() {
+            let yaml = std::str::from_utf8(&rest[..offset])?.to_string();
+            return Ok((Some(yaml), &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    bail!("Unterminated remotefs front matter: expected a closing `---` line");
+}
+
+/// The marker that begins a digest stub, followed by the checksum algorithm, E.G.:
+/// `#% remotefs-sha256: 9f86d081884c7d65...`
+/// Files in write_only mounts are imported as a digest stub instead of their contents.
+pub const DIGEST_MARKER: &str = "#% remotefs-";
+
+pub fn digest_stub(checksum: RemoteFsChecksum, digest: &str) -> Vec<u8> {
+    format!("{}{}: {}\n", DIGEST_MARKER, checksum.name(), digest).into_bytes()
+}
+
+/// If `s` is a digest stub, return the checksum algorithm and digest it records.
+pub fn parse_digest_stub(s: &[u8]) -> Option<(RemoteFsChecksum, String)> {
+    let s = std::str::from_utf8(s).ok()?;
+    let (name, digest) = s.strip_prefix(DIGEST_MARKER)?.split_once(':')?;
+    let checksum = RemoteFsChecksum::from_name(name)?;
+    checksum.parse_output(digest).map(|digest| (checksum, digest))
+}
+
+/// Begins the text of a provenance comment, E.G. `# Managed by autoschematic: prod/remotefs/web-01/etc/motd @ 3f9c2e1`.
+pub const PROVENANCE_MARKER: &str = "Managed by autoschematic:";
+
+/// Render a provenance comment for `source` in the style of `comment`, where `{}` stands for the text.
+fn provenance_line(comment: &str, source: &str) -> String {
+    comment.replacen("{}", &format!("{} {}", PROVENANCE_MARKER, source), 1)
+}
+
+/// Whether a line of the file must stay above the provenance comment, E.G. a shebang or an XML declaration.
+fn must_lead(line: &str) -> bool {
+    line.starts_with("#!") || line.starts_with("<?xml")
+}
+
+/// Insert a provenance comment for `source` at the top of `contents`, below any shebang or XML declaration.
+/// Contents that aren't UTF-8 are left alone.
+pub fn add_provenance(contents: &[u8], comment: &str, source: &str) -> Vec<u8> {
+    let Ok(text) = std::str::from_utf8(contents) else {
+        return contents.to_vec();
+    };
+    let at = match text.split_inclusive('\n').next() {
+        Some(first) if must_lead(first) => first.len(),
+        _ => 0,
+    };
+    let mut res = String::with_capacity(text.len() + 128);
+    res.push_str(&text[..at]);
+    if at > 0 && !res.ends_with('\n') {
+        res.push('\n');
+    }
+    res.push_str(&provenance_line(comment, source));
+    res.push('\n');
+    res.push_str(&text[at..]);
+    res.into_bytes()
+}
+
+/// Remove a provenance comment written by add_provenance in the style of `comment`, whatever its source,
+/// from among the first few lines of `contents` (which may still carry a metadata header).
+pub fn strip_provenance(contents: &[u8], comment: &str) -> Vec<u8> {
+    let Some((before, after)) = comment.split_once("{}") else {
+        return contents.to_vec();
+    };
+    let opening = format!("{}{}", before, PROVENANCE_MARKER);
+    let mut offset = 0;
+    for line in contents.split_inclusive(|b| *b == b'\n').take(3) {
+        let text = String::from_utf8_lossy(line);
+        let text = text.trim_end_matches(['\r', '\n']);
+        if text.starts_with(&opening) && text.ends_with(after) {
+            let mut res = contents[..offset].to_vec();
+            res.extend_from_slice(&contents[offset + line.len()..]);
+            return res;
+        }
+        offset += line.len();
+    }
+    contents.to_vec()
+}
+
+/// Compare two file bodies, where either may be a digest stub standing in for the real contents.
+/// Stubs recorded with different checksum algorithms never match.
+pub fn contents_match(a: &[u8], b: &[u8]) -> bool {
+    match (parse_digest_stub(a), parse_digest_stub(b)) {
+        (Some(a), Some(b)) => a == b,
+        (Some((checksum, a)), None) => a == checksum.digest(b),
+        (None, Some((checksum, b))) => checksum.digest(a) == b,
+        (None, None) => a == b,
+    }
+}
+
+/// The body of a remote file, with its metadata header if any.
+/// A zero-length `contents` is an empty file that exists: wherever a file may be absent,
+/// that is represented by `None` around the FileContents, never by an empty body.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileContents {
-    pub contents: Vec<u8>,
+    /// The YAML of the file's front matter in the repo, if any, carried along so that it survives a round trip.
+    #[serde(default)]
+    pub front_matter: Option<String>,
+    pub header:       Option<FileHeader>,
+    pub contents:     Vec<u8>,
+}
+
+impl FileContents {
+    /// Split a leading front-matter block (if any) off of the raw file body, and then a metadata header (if any)
+    /// if `metadata_header` is set, as it is for files in mounts with `metadata_header: true`.
+    /// In other mounts, a first line that looks like a header is just part of the contents.
+    pub fn parse(s: &[u8], metadata_header: bool) -> Result<Self, anyhow::Error> {
+        let (front_matter, s) = split_front_matter(s)?;
+        if !metadata_header || !s.starts_with(HEADER_MARKER.as_bytes()) {
+            return Ok(FileContents {
+                front_matter,
+                header: None,
+                contents: s.to_vec(),
+            });
+        }
+
+        let (line, rest) = match s.iter().position(|b| *b == b'\n') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => (s, &s[s.len()..]),
+        };
+
+        Ok(FileContents {
+            front_matter,
+            header: Some(FileHeader::parse(std::str::from_utf8(line)?)?),
+            contents: rest.to_vec(),
+        })
+    }
+
+    /// Parse the options in this file's front matter, if it has any.
+    pub fn front_matter(&self) -> Result<Option<FrontMatter>, anyhow::Error> {
+        match &self.front_matter {
+            Some(yaml) if yaml.trim().is_empty() => Ok(Some(FrontMatter::default())),
+            Some(yaml) => Ok(Some(serde_yaml::from_str(yaml)?)),
+            None => Ok(None),
+        }
+    }
 }
 
 impl Resource for FileContents {
     fn to_bytes(&self) -> Result<Vec<u8>, anyhow::Error> {
-        Ok(self.contents.clone())
+        let mut res = Vec::new();
+        if let Some(yaml) = &self.front_matter {
+            res.extend_from_slice(FRONT_MATTER_OPEN.as_bytes());
+            res.extend_from_slice(yaml.as_bytes());
+            res.extend_from_slice(FRONT_MATTER_CLOSE.as_bytes());
+        }
+        if let Some(header) = &self.header {
+            res.extend_from_slice(header.render().as_bytes());
+        }
+        res.extend_from_slice(&self.contents);
+        Ok(res)
     }
 
     fn from_bytes(addr: &impl ResourceAddress, s: &[u8]) -> Result<Self, anyhow::Error>
     where
         Self: Sized,
     {
-        Ok(FileContents { contents: s.to_vec() })
+        // Without the file's mount at hand, a header can't be told from contents, so it is left in them.
+        FileContents::parse(s, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_round_trip() {
+        let header = FileHeader {
+            mode: Some(0o644),
+            uid:  Some(0),
+            gid:  Some(1000),
+        };
+        let line = header.render();
+        assert_eq!(line, "#% remotefs: mode=0644 uid=0 gid=1000\n");
+        assert_eq!(FileHeader::parse(&line).unwrap(), header);
+    }
+
+    #[test]
+    fn headers_may_omit_fields() {
+        let setuid = FileHeader {
+            mode: Some(0o4755),
+            ..Default::default()
+        };
+        assert_eq!(setuid.render(), "#% remotefs: mode=4755\n");
+        assert_eq!(FileHeader::parse("#% remotefs: mode=4755").unwrap(), setuid);
+        assert_eq!(FileHeader::parse("#% remotefs:").unwrap(), FileHeader::default());
+        assert_eq!(FileHeader::parse("#% remotefs: mode=0o600").unwrap().mode, Some(0o600));
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        assert!(FileHeader::parse("# remotefs: mode=0644").is_err());
+        assert!(FileHeader::parse("#% remotefs: mode=0948").is_err());
+        assert!(FileHeader::parse("#% remotefs: uid=root").is_err());
+        assert!(FileHeader::parse("#% remotefs: gid=-1").is_err());
+        assert!(FileHeader::parse("#% remotefs: owner=0").is_err());
+        assert!(FileHeader::parse("#% remotefs: mode").is_err());
+    }
+
+    #[test]
+    fn headers_are_split_off_only_when_enabled() {
+        let body = b"#% remotefs: mode=0600 uid=0\nsecret\n";
+        let parsed = FileContents::parse(body, true).unwrap();
+        assert_eq!(
+            parsed.header,
+            Some(FileHeader {
+                mode: Some(0o600),
+                uid:  Some(0),
+                gid:  None,
+            })
+        );
+        assert_eq!(parsed.contents, b"secret\n");
+        assert_eq!(parsed.to_bytes().unwrap(), body);
+
+        let unparsed = FileContents::parse(body, false).unwrap();
+        assert_eq!(unparsed.header, None);
+        assert_eq!(unparsed.contents, body);
+
+        assert!(FileContents::parse(b"#% remotefs: mode=rw\nsecret\n", true).is_err());
+        assert!(FileContents::parse(b"#% remotefs: mode=0644", true).unwrap().contents.is_empty());
+    }
+
+    #[test]
+    fn digest_stubs_round_trip() {
+        let digest = RemoteFsChecksum::Sha256.digest(b"test");
+        let stub = digest_stub(RemoteFsChecksum::Sha256, &digest);
+        assert_eq!(stub, format!("#% remotefs-sha256: {}\n", digest).into_bytes());
+        assert_eq!(parse_digest_stub(&stub), Some((RemoteFsChecksum::Sha256, digest)));
+
+        let upper = b"#% remotefs-md5: 098F6BCD4621D373CADE4E832627B4F6\n";
+        assert_eq!(
+            parse_digest_stub(upper),
+            Some((RemoteFsChecksum::Md5, "098f6bcd4621d373cade4e832627b4f6".to_string()))
+        );
+    }
+
+    #[test]
+    fn malformed_digest_stubs_are_rejected() {
+        let md5 = RemoteFsChecksum::Md5.digest(b"test");
+        assert_eq!(parse_digest_stub(format!("#% remotefs-sha256: {}", md5).as_bytes()), None);
+        assert_eq!(parse_digest_stub(format!("#% remotefs-crc32: {}", md5).as_bytes()), None);
+        assert_eq!(parse_digest_stub(b"#% remotefs-md5: not a digest"), None);
+        assert_eq!(parse_digest_stub(b"#% remotefs-md5"), None);
+        assert_eq!(parse_digest_stub(b"#% remotefs: mode=0644"), None);
+        assert_eq!(parse_digest_stub(b"\xff#% remotefs-md5"), None);
+    }
+
+    #[test]
+    fn stubs_match_the_contents_they_digest() {
+        let sha256 = digest_stub(RemoteFsChecksum::Sha256, &RemoteFsChecksum::Sha256.digest(b"test"));
+        let md5 = digest_stub(RemoteFsChecksum::Md5, &RemoteFsChecksum::Md5.digest(b"test"));
+        assert!(contents_match(&sha256, b"test"));
+        assert!(contents_match(b"test", &md5));
+        assert!(contents_match(&sha256, &sha256));
+        assert!(!contents_match(&sha256, b"test\n"));
+        assert!(!contents_match(&sha256, &md5));
+    }
+
+    #[test]
+    fn provenance_round_trips() {
+        let with = add_provenance(b"hello\n", "# {}", "prod/remotefs/web-01/etc/motd @ 3f9c2e1");
+        assert_eq!(with, b"# Managed by autoschematic: prod/remotefs/web-01/etc/motd @ 3f9c2e1\nhello\n");
+        assert_eq!(strip_provenance(&with, "# {}"), b"hello\n");
+
+        let xml = b"<?xml version=\"1.0\"?>\n<config/>\n";
+        let with = add_provenance(xml, "<!-- {} -->", "a @ b");
+        assert_eq!(with, b"<?xml version=\"1.0\"?>\n<!-- Managed by autoschematic: a @ b -->\n<config/>\n");
+        assert_eq!(strip_provenance(&with, "<!-- {} -->"), xml);
+    }
+
+    #[test]
+    fn provenance_stays_below_a_shebang() {
+        let script = b"#!/bin/sh\necho hi\n";
+        let with = add_provenance(script, "# {}", "a @ b");
+        assert_eq!(with, b"#!/bin/sh\n# Managed by autoschematic: a @ b\necho hi\n");
+        assert_eq!(strip_provenance(&with, "# {}"), script);
+
+        assert_eq!(add_provenance(b"#!/bin/sh", "# {}", "a @ b"), b"#!/bin/sh\n# Managed by autoschematic: a @ b\n");
+    }
+
+    #[test]
+    fn provenance_is_stripped_whatever_its_source() {
+        assert_eq!(strip_provenance(b"# Managed by autoschematic: old @ 1234567\r\nhi\r\n", "# {}"), b"hi\r\n");
+        let header = b"#% remotefs: mode=0644\n# Managed by autoschematic: x\nhi\n";
+        assert_eq!(strip_provenance(header, "# {}"), b"#% remotefs: mode=0644\nhi\n");
+    }
+
+    #[test]
+    fn other_contents_are_left_alone() {
+        let binary = [0xff, 0xfe, b'\n'];
+        assert_eq!(add_provenance(&binary, "# {}", "a @ b"), binary);
+
+        let deep = b"a\nb\nc\n# Managed by autoschematic: x\n";
+        assert_eq!(strip_provenance(deep, "# {}"), deep);
+        assert_eq!(strip_provenance(b"// Managed by autoschematic: x\n", "# {}"), b"// Managed by autoschematic: x\n");
+        assert_eq!(strip_provenance(b"# Managed by autoschematic: x\n", "#"), b"# Managed by autoschematic: x\n");
     }
 }
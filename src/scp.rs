@@ -0,0 +1,204 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use remotefs::{
+    File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult,
+    fs::{Metadata, ReadStream, UnixPex, Welcome, WriteStream},
+};
+use ssh2::{Channel, Session};
+
+use crate::{
+    shell::{self, ShellFs},
+    util::shell_quote_path,
+};
+
+/// A RemoteFs over SCP on an SSH session that the connector logged in to itself. File contents are
+/// transferred with SCP, and everything else is a shell command run over an exec channel, as with KubeFs.
+pub struct SessionScpFs {
+    session:   Session,
+    connected: bool,
+    wd:        PathBuf,
+    /// The SCP channel behind the stream last returned by open or create,
+    /// with the metadata to apply to a created file once it has been written.
+    pending:   Option<(Channel, Option<(PathBuf, Metadata)>)>,
+}
+
+/// Run `script` over an exec channel of `session`, returning its exit code, stdout and stderr.
+pub fn session_exec(session: &Session, script: &str) -> RemoteResult<(u32, String, String)> {
+    let protocol_error = |e: ssh2::Error| RemoteError::new_ex(RemoteErrorType::ProtocolError, e);
+    let io_error = |e: std::io::Error| RemoteError::new_ex(RemoteErrorType::IoError, e);
+    let mut channel = session.channel_session().map_err(protocol_error)?;
+    channel.exec(script).map_err(protocol_error)?;
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    channel.read_to_end(&mut stdout).map_err(io_error)?;
+    channel.stderr().read_to_end(&mut stderr).map_err(io_error)?;
+    channel.wait_close().map_err(protocol_error)?;
+    let code = channel.exit_status().map_err(protocol_error)?;
+    Ok((
+        code as u32,
+        String::from_utf8_lossy(&stdout).into_owned(),
+        String::from_utf8_lossy(&stderr).into_owned(),
+    ))
+}
+
+impl SessionScpFs {
+    /// Wrap `session`, which must already be authenticated.
+    pub fn new(session: Session) -> Self {
+        SessionScpFs {
+            session,
+            connected: false,
+            wd: PathBuf::from("/"),
+            pending: None,
+        }
+    }
+
+    fn absolute(&self, path: &Path) -> PathBuf {
+        self.wd.join(path)
+    }
+
+    /// Close the SCP channel behind the last stream, once its transfer is complete.
+    fn finish_pending(&mut self, error: RemoteErrorType) -> RemoteResult<Option<(PathBuf, Metadata)>> {
+        let Some((mut channel, metadata)) = self.pending.take() else {
+            return Ok(None);
+        };
+        let failed = |e: ssh2::Error| RemoteError::new_ex(error, e);
+        channel.send_eof().map_err(failed)?;
+        channel.wait_eof().map_err(failed)?;
+        channel.close().map_err(failed)?;
+        channel.wait_close().map_err(failed)?;
+        Ok(metadata)
+    }
+}
+
+impl ShellFs for SessionScpFs {
+    fn run_checked(&self, script: &str, error: RemoteErrorType) -> RemoteResult<String> {
+        let (code, stdout, stderr) = session_exec(&self.session, script)?;
+        if code != 0 {
+            return Err(RemoteError::new_ex(error, format!("`{}`: {}", script, stderr.trim())));
+        }
+        Ok(stdout)
+    }
+}
+
+impl RemoteFs for SessionScpFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let wd = self.run_checked("pwd", RemoteErrorType::ConnectionError)?;
+        self.wd = PathBuf::from(wd.trim());
+        self.connected = true;
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.pending = None;
+        self.connected = false;
+        let _ = self.session.disconnect(None, "", None);
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.connected
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wd.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        self.wd = shell::change_dir(self, &self.absolute(dir))?;
+        Ok(self.wd.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        shell::list_dir(self, &self.absolute(path))
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        shell::stat(self, &self.absolute(path))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        shell::setstat(self, &self.absolute(path), metadata)
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        shell::exists(self, &self.absolute(path))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        shell::remove_file(self, &self.absolute(path))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        shell::remove_dir(self, &self.absolute(path))
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        shell::remove_dir_all(self, &self.absolute(path))
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        shell::create_dir(self, &self.absolute(path), mode)
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        shell::symlink(self, &self.absolute(path), target)
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        shell::copy(self, &self.absolute(src), &self.absolute(dest))
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        shell::mov(self, &self.absolute(src), &self.absolute(dest))
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        let script = format!("cd {} && {}", shell_quote_path(&self.wd), cmd);
+        let (code, stdout, _) = session_exec(&self.session, &script)?;
+        Ok((code, stdout))
+    }
+
+    fn append(&mut self, _path: &Path, _metadata: &Metadata) -> RemoteResult<WriteStream> {
+        Err(RemoteError::new_ex(RemoteErrorType::UnsupportedFeature, "SCP can't append to a file"))
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        let path = self.absolute(path);
+        let mode = metadata.mode.map(u32::from).unwrap_or(0o644);
+        // SCP sends the size up front, so it must be the exact size of what is written.
+        let channel = self
+            .session
+            .scp_send(&path, mode as i32, metadata.size, None)
+            .map_err(|e| RemoteError::new_ex(RemoteErrorType::FileCreateDenied, e))?;
+        let stream = channel.stream(0);
+        self.pending = Some((channel, Some((path, metadata.clone()))));
+        Ok(WriteStream::from(Box::new(stream) as Box<dyn Write + Send>))
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let (channel, stat) = self
+            .session
+            .scp_recv(&self.absolute(path))
+            .map_err(|e| RemoteError::new_ex(RemoteErrorType::CouldNotOpenFile, e))?;
+        // The file is followed on the channel by the end of the SCP exchange, which isn't part of it.
+        let stream = channel.stream(0).take(stat.size());
+        self.pending = Some((channel, None));
+        Ok(ReadStream::from(Box::new(stream) as Box<dyn Read + Send>))
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        drop(readable);
+        self.finish_pending(RemoteErrorType::CouldNotOpenFile)?;
+        Ok(())
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        drop(writable);
+        if let Some((path, metadata)) = self.finish_pending(RemoteErrorType::FileCreateDenied)? {
+            self.setstat(&path, metadata)?;
+        }
+        Ok(())
+    }
+}
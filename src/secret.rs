@@ -0,0 +1,101 @@
+use std::{path::Path, process::Command};
+
+use anyhow::{Context, bail};
+
+/// Marks a config value as a reference to a secret, which is resolved on the connector's side
+/// only when an op is executed, so that the secret itself never appears in config or plans.
+/// E.G. `secret:env:DEPLOY_TOKEN`, or `secret:file:secrets/deploy_token` (relative to the prefix).
+/// Secrets can also be fetched from a secret store with its usual CLI, which must be installed and logged in:
+/// - `secret:vault:<path>[#<field>]` reads a field (by default `value`) of a Vault KV secret with `vault kv get`,
+///   E.G. `secret:vault:kv/ssh/prod#private_key`.
+/// - `secret:aws:<secret id>[#<key>]` reads an AWS Secrets Manager secret with `aws secretsmanager`,
+///   or one key of it if the secret is a JSON object, E.G. `secret:aws:prod/ssh#password`.
+/// - `secret:keyring:<service>/<account>` reads a password from the local keyring, with `security`
+///   on macOS and `secret-tool` elsewhere, E.G. `secret:keyring:remotefs/deploy`.
+pub const SECRET_PREFIX: &str = "secret:";
+
+/// Resolve `value` if it is a secret reference, or return it unchanged.
+pub fn resolve(value: &str, prefix: &Path) -> Result<String, anyhow::Error> {
+    let Some(reference) = value.strip_prefix(SECRET_PREFIX) else {
+        return Ok(value.to_string());
+    };
+
+    match reference.split_once(':') {
+        Some(("env", name)) => std::env::var(name).with_context(|| format!("Secret {} is not set", value)),
+        Some(("file", path)) => {
+            let body =
+                std::fs::read_to_string(prefix.join(path)).with_context(|| format!("Failed to read secret {}", value))?;
+            Ok(body.trim_end_matches(['\r', '\n']).to_string())
+        }
+        Some(("vault", path)) => {
+            let (path, field) = path.split_once('#').unwrap_or((path, "value"));
+            run(Command::new("vault").args(["kv", "get", &format!("-field={}", field), path]))
+                .with_context(|| format!("Failed to read secret {}", value))
+        }
+        Some(("aws", id)) => {
+            let (id, key) = match id.split_once('#') {
+                Some((id, key)) => (id, Some(key)),
+                None => (id, None),
+            };
+            let secret = run(Command::new("aws").args([
+                "secretsmanager",
+                "get-secret-value",
+                "--secret-id",
+                id,
+                "--query",
+                "SecretString",
+                "--output",
+                "text",
+            ]))
+            .with_context(|| format!("Failed to read secret {}", value))?;
+            let Some(key) = key else {
+                return Ok(secret);
+            };
+            let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&secret)
+                .with_context(|| format!("Secret {} is not a JSON object", value))?;
+            match object.get(key) {
+                Some(serde_json::Value::String(s)) => Ok(s.clone()),
+                Some(other) => Ok(other.to_string()),
+                None => bail!("Secret {} has no key {}", value, key),
+            }
+        }
+        Some(("keyring", entry)) => {
+            let Some((service, account)) = entry.split_once('/') else {
+                bail!("Invalid secret reference {}: expected secret:keyring:<service>/<account>", value);
+            };
+            let mut command = if cfg!(target_os = "macos") {
+                let mut command = Command::new("security");
+                command.args(["find-generic-password", "-s", service, "-a", account, "-w"]);
+                command
+            } else {
+                let mut command = Command::new("secret-tool");
+                command.args(["lookup", "service", service, "account", account]);
+                command
+            };
+            run(&mut command).with_context(|| format!("Failed to read secret {}", value))
+        }
+        _ => bail!(
+            "Unknown secret reference {}: expected secret:env:<NAME>, secret:file:<path>, secret:vault:<path>, \
+             secret:aws:<secret id> or secret:keyring:<service>/<account>",
+            value
+        ),
+    }
+}
+
+/// Run a secret store's CLI, returning its output without the trailing newline.
+fn run(command: &mut Command) -> Result<String, anyhow::Error> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .with_context(|| format!("Failed to run {}. Is it installed?", program))?;
+    if !output.status.success() {
+        bail!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let stdout = String::from_utf8(output.stdout).with_context(|| format!("{} printed a secret that isn't UTF-8", program))?;
+    Ok(stdout.trim_end_matches(['\r', '\n']).to_string())
+}
@@ -0,0 +1,304 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use remotefs::{
+    File, RemoteError, RemoteErrorType, RemoteFs, RemoteResult,
+    fs::{FileType, Metadata, ReadStream, UnixPex, Welcome, WriteStream},
+};
+use ssh2::{ErrorCode, FileStat, OpenFlags, OpenType, Session, Sftp};
+
+use crate::{scp::session_exec, util::shell_quote_path};
+
+/// The SFTP status code for a missing file (SSH_FX_NO_SUCH_FILE).
+const SFTP_NO_SUCH_FILE: i32 = 2;
+
+/// A RemoteFs over SFTP on an SSH session that the connector logged in to itself.
+/// Remote commands run over exec channels of the same session.
+pub struct SessionSftpFs {
+    session: Session,
+    sftp:    Option<Sftp>,
+    wd:      PathBuf,
+    /// The file behind the stream last returned by create or append,
+    /// with the metadata to apply to it once it has been written.
+    pending: Option<(PathBuf, Metadata)>,
+}
+
+/// Map an SFTP error to the kind the connector checks for, if it is one, or else to `kind`.
+fn sftp_error(e: ssh2::Error, kind: RemoteErrorType) -> RemoteError {
+    let kind = match e.code() {
+        ErrorCode::SFTP(SFTP_NO_SUCH_FILE) => RemoteErrorType::NoSuchFileOrDirectory,
+        // The session itself failed, E.G. its connection dropped.
+        ErrorCode::Session(_) => RemoteErrorType::ProtocolError,
+        ErrorCode::SFTP(_) => kind,
+    };
+    RemoteError::new_ex(kind, e)
+}
+
+fn to_secs(time: SystemTime) -> Option<u64> {
+    time.duration_since(SystemTime::UNIX_EPOCH).ok().map(|duration| duration.as_secs())
+}
+
+impl SessionSftpFs {
+    /// Wrap `session`, which must already be authenticated.
+    pub fn new(session: Session) -> Self {
+        SessionSftpFs {
+            session,
+            sftp: None,
+            wd: PathBuf::from("/"),
+            pending: None,
+        }
+    }
+
+    fn sftp(&self) -> RemoteResult<&Sftp> {
+        self.sftp.as_ref().ok_or_else(|| RemoteError::new(RemoteErrorType::NotConnected))
+    }
+
+    fn absolute(&self, path: &Path) -> PathBuf {
+        self.wd.join(path)
+    }
+
+    fn file(path: PathBuf, stat: &FileStat, symlink: Option<PathBuf>) -> File {
+        let file_type = match stat.perm.map(|perm| perm & 0o170000) {
+            Some(0o040000) => FileType::Directory,
+            Some(0o120000) => FileType::Symlink,
+            _ => FileType::File,
+        };
+        File {
+            path,
+            metadata: Metadata {
+                accessed: stat.atime.map(|atime| SystemTime::UNIX_EPOCH + Duration::from_secs(atime)),
+                created: None,
+                modified: stat.mtime.map(|mtime| SystemTime::UNIX_EPOCH + Duration::from_secs(mtime)),
+                uid: stat.uid,
+                gid: stat.gid,
+                mode: stat.perm.map(|perm| UnixPex::from(perm & 0o777)),
+                size: stat.size.unwrap_or(0),
+                symlink,
+                file_type,
+            },
+        }
+    }
+
+    /// The target of `path`, if `stat` (from an lstat of it) is a symlink's.
+    fn symlink_target(&self, path: &Path, stat: &FileStat) -> RemoteResult<Option<PathBuf>> {
+        if stat.perm.is_some_and(|perm| perm & 0o170000 == 0o120000) {
+            let target = self.sftp()?.readlink(path).map_err(|e| sftp_error(e, RemoteErrorType::StatFailed))?;
+            return Ok(Some(target));
+        }
+        Ok(None)
+    }
+
+    fn write_stream(&mut self, path: &Path, metadata: &Metadata, append: bool) -> RemoteResult<WriteStream> {
+        let path = self.absolute(path);
+        let flags = OpenFlags::WRITE | OpenFlags::CREATE | if append { OpenFlags::APPEND } else { OpenFlags::TRUNCATE };
+        let mode = metadata.mode.map(u32::from).unwrap_or(0o644);
+        let file = self
+            .sftp()?
+            .open_mode(&path, flags, mode as i32, OpenType::File)
+            .map_err(|e| sftp_error(e, RemoteErrorType::FileCreateDenied))?;
+        self.pending = Some((path, metadata.clone()));
+        Ok(WriteStream::from(Box::new(file) as Box<dyn Write + Send>))
+    }
+}
+
+impl RemoteFs for SessionSftpFs {
+    fn connect(&mut self) -> RemoteResult<Welcome> {
+        let sftp = self
+            .session
+            .sftp()
+            .map_err(|e| RemoteError::new_ex(RemoteErrorType::ProtocolError, e))?;
+        self.wd = sftp
+            .realpath(Path::new("."))
+            .map_err(|e| sftp_error(e, RemoteErrorType::ConnectionError))?;
+        self.sftp = Some(sftp);
+        Ok(Welcome::default())
+    }
+
+    fn disconnect(&mut self) -> RemoteResult<()> {
+        self.pending = None;
+        self.sftp = None;
+        let _ = self.session.disconnect(None, "", None);
+        Ok(())
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.sftp.is_some()
+    }
+
+    fn pwd(&mut self) -> RemoteResult<PathBuf> {
+        Ok(self.wd.clone())
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> RemoteResult<PathBuf> {
+        let sftp = self.sftp()?;
+        let dir = sftp
+            .realpath(&self.absolute(dir))
+            .map_err(|e| sftp_error(e, RemoteErrorType::NoSuchFileOrDirectory))?;
+        if !sftp.stat(&dir).map_err(|e| sftp_error(e, RemoteErrorType::StatFailed))?.is_dir() {
+            return Err(RemoteError::new_ex(RemoteErrorType::BadFile, "not a directory"));
+        }
+        self.wd = dir;
+        Ok(self.wd.clone())
+    }
+
+    fn list_dir(&mut self, path: &Path) -> RemoteResult<Vec<File>> {
+        let path = self.absolute(path);
+        let entries = self
+            .sftp()?
+            .readdir(&path)
+            .map_err(|e| sftp_error(e, RemoteErrorType::NoSuchFileOrDirectory))?;
+        entries
+            .into_iter()
+            .map(|(path, stat)| {
+                let symlink = self.symlink_target(&path, &stat)?;
+                Ok(Self::file(path, &stat, symlink))
+            })
+            .collect()
+    }
+
+    fn stat(&mut self, path: &Path) -> RemoteResult<File> {
+        let path = self.absolute(path);
+        let stat = self.sftp()?.lstat(&path).map_err(|e| sftp_error(e, RemoteErrorType::StatFailed))?;
+        let symlink = self.symlink_target(&path, &stat)?;
+        Ok(Self::file(path, &stat, symlink))
+    }
+
+    fn setstat(&mut self, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+        let path = self.absolute(path);
+        let sftp = self.sftp()?;
+        // Ownership first, since changing it may clear the setuid and setgid bits of the mode.
+        if metadata.uid.is_some() || metadata.gid.is_some() {
+            let stat = FileStat {
+                size:  None,
+                uid:   metadata.uid,
+                gid:   metadata.gid,
+                perm:  None,
+                atime: None,
+                mtime: None,
+            };
+            sftp.setstat(&path, stat).map_err(|e| sftp_error(e, RemoteErrorType::PexError))?;
+        }
+        let mtime = metadata.modified.and_then(to_secs);
+        let stat = FileStat {
+            size:  None,
+            uid:   None,
+            gid:   None,
+            perm:  metadata.mode.map(u32::from),
+            // SFTP sets both times or neither.
+            atime: metadata.accessed.and_then(to_secs).or(mtime),
+            mtime,
+        };
+        if stat.perm.is_some() || stat.mtime.is_some() {
+            sftp.setstat(&path, stat).map_err(|e| sftp_error(e, RemoteErrorType::PexError))?;
+        }
+        Ok(())
+    }
+
+    fn exists(&mut self, path: &Path) -> RemoteResult<bool> {
+        match self.sftp()?.lstat(&self.absolute(path)) {
+            Ok(_) => Ok(true),
+            Err(e) if e.code() == ErrorCode::SFTP(SFTP_NO_SUCH_FILE) => Ok(false),
+            Err(e) => Err(sftp_error(e, RemoteErrorType::StatFailed)),
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> RemoteResult<()> {
+        self.sftp()?
+            .unlink(&self.absolute(path))
+            .map_err(|e| sftp_error(e, RemoteErrorType::CouldNotRemoveFile))
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> RemoteResult<()> {
+        self.sftp()?
+            .rmdir(&self.absolute(path))
+            .map_err(|e| sftp_error(e, RemoteErrorType::CouldNotRemoveFile))
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> RemoteResult<()> {
+        let path = self.absolute(path);
+        let entries = self
+            .sftp()?
+            .readdir(&path)
+            .map_err(|e| sftp_error(e, RemoteErrorType::CouldNotRemoveFile))?;
+        for (entry, stat) in entries {
+            // The entries are lstat'd, so a symlink to a directory is removed rather than followed.
+            if stat.is_dir() {
+                self.remove_dir_all(&entry)?;
+            } else {
+                self.remove_file(&entry)?;
+            }
+        }
+        self.remove_dir(&path)
+    }
+
+    fn create_dir(&mut self, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+        if self.exists(path)? {
+            return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists));
+        }
+        self.sftp()?
+            .mkdir(&self.absolute(path), u32::from(mode) as i32)
+            .map_err(|e| sftp_error(e, RemoteErrorType::FileCreateDenied))
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> RemoteResult<()> {
+        // ssh2 creates the link at its second argument, pointing at its first.
+        self.sftp()?
+            .symlink(target, &self.absolute(path))
+            .map_err(|e| sftp_error(e, RemoteErrorType::FileCreateDenied))
+    }
+
+    fn copy(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        // SFTP has no copy, so this takes exec, as it does with SftpFs.
+        let (src_q, dest_q) = (shell_quote_path(&self.absolute(src)), shell_quote_path(&self.absolute(dest)));
+        let (code, output) = self.exec(&format!("cp -a -- {} {}", src_q, dest_q))?;
+        if code != 0 {
+            return Err(RemoteError::new_ex(RemoteErrorType::FileCreateDenied, output));
+        }
+        Ok(())
+    }
+
+    fn mov(&mut self, src: &Path, dest: &Path) -> RemoteResult<()> {
+        self.sftp()?
+            .rename(&self.absolute(src), &self.absolute(dest), None)
+            .map_err(|e| sftp_error(e, RemoteErrorType::FileCreateDenied))
+    }
+
+    fn exec(&mut self, cmd: &str) -> RemoteResult<(u32, String)> {
+        let script = format!("cd {} && {}", shell_quote_path(&self.wd), cmd);
+        let (code, stdout, _) = session_exec(&self.session, &script)?;
+        Ok((code, stdout))
+    }
+
+    fn append(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.write_stream(path, metadata, true)
+    }
+
+    fn create(&mut self, path: &Path, metadata: &Metadata) -> RemoteResult<WriteStream> {
+        self.write_stream(path, metadata, false)
+    }
+
+    fn open(&mut self, path: &Path) -> RemoteResult<ReadStream> {
+        let file = self
+            .sftp()?
+            .open(&self.absolute(path))
+            .map_err(|e| sftp_error(e, RemoteErrorType::CouldNotOpenFile))?;
+        Ok(ReadStream::from(Box::new(file) as Box<dyn Read + Send>))
+    }
+
+    fn on_read(&mut self, readable: ReadStream) -> RemoteResult<()> {
+        drop(readable);
+        Ok(())
+    }
+
+    fn on_written(&mut self, writable: WriteStream) -> RemoteResult<()> {
+        // Dropping the handle closes the file, which completes the upload.
+        drop(writable);
+        if let Some((path, metadata)) = self.pending.take() {
+            self.setstat(&path, metadata)?;
+        }
+        Ok(())
+    }
+}
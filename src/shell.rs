@@ -0,0 +1,167 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use remotefs::{
+    File, RemoteError, RemoteErrorType, RemoteResult,
+    fs::{FileType, Metadata, UnixPex},
+};
+
+use crate::util::shell_quote_path;
+
+/// A remote end that file operations can be performed on with shell commands, as KubeFs does over
+/// `kubectl exec` and SessionScpFs over SSH exec channels. The functions below take absolute paths.
+pub trait ShellFs {
+    /// Run `script` under sh on the remote end, failing with `error` if it exits nonzero.
+    fn run_checked(&self, script: &str, error: RemoteErrorType) -> RemoteResult<String>;
+}
+
+pub fn protocol_error(msg: impl ToString) -> RemoteError {
+    RemoteError::new_ex(RemoteErrorType::ProtocolError, msg)
+}
+
+/// Parse a line of `stat -c '%f %s %u %g %X %Y %n'` output.
+pub fn parse_stat(line: &str, symlink: Option<PathBuf>) -> RemoteResult<File> {
+    let mut fields = line.splitn(7, ' ');
+    let mut next = || fields.next().ok_or_else(|| protocol_error(format!("Unexpected stat output: {}", line)));
+    let raw_mode = u32::from_str_radix(next()?, 16).map_err(protocol_error)?;
+    let size: u64 = next()?.parse().map_err(protocol_error)?;
+    let uid: u32 = next()?.parse().map_err(protocol_error)?;
+    let gid: u32 = next()?.parse().map_err(protocol_error)?;
+    let atime: u64 = next()?.parse().map_err(protocol_error)?;
+    let mtime: u64 = next()?.parse().map_err(protocol_error)?;
+    let path = PathBuf::from(next()?);
+
+    let file_type = match raw_mode & 0o170000 {
+        0o040000 => FileType::Directory,
+        0o120000 => FileType::Symlink,
+        _ => FileType::File,
+    };
+    Ok(File {
+        path,
+        metadata: Metadata {
+            accessed: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(atime)),
+            created: None,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime)),
+            uid: Some(uid),
+            gid: Some(gid),
+            mode: Some(UnixPex::from(raw_mode & 0o777)),
+            size,
+            symlink,
+            file_type,
+        },
+    })
+}
+
+/// The canonical path of the directory `dir`.
+pub fn change_dir(fs: &impl ShellFs, dir: &Path) -> RemoteResult<PathBuf> {
+    let wd = fs.run_checked(
+        &format!("cd {} && pwd", shell_quote_path(dir)),
+        RemoteErrorType::NoSuchFileOrDirectory,
+    )?;
+    Ok(PathBuf::from(wd.trim()))
+}
+
+pub fn list_dir(fs: &impl ShellFs, path: &Path) -> RemoteResult<Vec<File>> {
+    let output = fs.run_checked(
+        &format!(
+            "find {} -mindepth 1 -maxdepth 1 -exec stat -c '%f %s %u %g %X %Y %n' {{}} +",
+            shell_quote_path(path)
+        ),
+        RemoteErrorType::NoSuchFileOrDirectory,
+    )?;
+    output.lines().map(|line| parse_stat(line, None)).collect()
+}
+
+pub fn stat(fs: &impl ShellFs, path: &Path) -> RemoteResult<File> {
+    let path_q = shell_quote_path(path);
+    let output = fs.run_checked(
+        &format!("stat -c '%f %s %u %g %X %Y %n' -- {path_q} && {{ readlink -- {path_q} || true; }}"),
+        RemoteErrorType::StatFailed,
+    )?;
+    let mut lines = output.lines();
+    let Some(line) = lines.next() else {
+        return Err(protocol_error("stat printed nothing"));
+    };
+    parse_stat(line, lines.next().map(PathBuf::from))
+}
+
+pub fn setstat(fs: &impl ShellFs, path: &Path, metadata: Metadata) -> RemoteResult<()> {
+    let path_q = shell_quote_path(path);
+    let mut cmds = Vec::new();
+    // Ownership first, since chown clears the setuid and setgid bits that chmod may set.
+    match (metadata.uid, metadata.gid) {
+        (Some(uid), Some(gid)) => cmds.push(format!("chown {}:{} -- {}", uid, gid, path_q)),
+        (Some(uid), None) => cmds.push(format!("chown {} -- {}", uid, path_q)),
+        (None, Some(gid)) => cmds.push(format!("chgrp {} -- {}", gid, path_q)),
+        (None, None) => {}
+    }
+    if let Some(mode) = metadata.mode {
+        cmds.push(format!("chmod {:o} -- {}", u32::from(mode), path_q));
+    }
+    if let Some(modified) = metadata.modified.and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok()) {
+        cmds.push(format!("touch -m -d @{} -- {}", modified.as_secs(), path_q));
+    }
+    if cmds.is_empty() {
+        return Ok(());
+    }
+    fs.run_checked(&cmds.join(" && "), RemoteErrorType::PexError)?;
+    Ok(())
+}
+
+pub fn exists(fs: &impl ShellFs, path: &Path) -> RemoteResult<bool> {
+    let path_q = shell_quote_path(path);
+    // The answer is printed, since a failure of the transport itself, E.G. kubectl's, is also a nonzero exit.
+    let output = fs.run_checked(
+        &format!("if test -e {path_q} || test -L {path_q}; then echo yes; else echo no; fi"),
+        RemoteErrorType::StatFailed,
+    )?;
+    Ok(output.trim() == "yes")
+}
+
+pub fn remove_file(fs: &impl ShellFs, path: &Path) -> RemoteResult<()> {
+    fs.run_checked(&format!("rm -- {}", shell_quote_path(path)), RemoteErrorType::CouldNotRemoveFile)?;
+    Ok(())
+}
+
+pub fn remove_dir(fs: &impl ShellFs, path: &Path) -> RemoteResult<()> {
+    fs.run_checked(&format!("rmdir -- {}", shell_quote_path(path)), RemoteErrorType::CouldNotRemoveFile)?;
+    Ok(())
+}
+
+pub fn remove_dir_all(fs: &impl ShellFs, path: &Path) -> RemoteResult<()> {
+    fs.run_checked(&format!("rm -rf -- {}", shell_quote_path(path)), RemoteErrorType::CouldNotRemoveFile)?;
+    Ok(())
+}
+
+pub fn create_dir(fs: &impl ShellFs, path: &Path, mode: UnixPex) -> RemoteResult<()> {
+    if exists(fs, path)? {
+        return Err(RemoteError::new(RemoteErrorType::DirectoryAlreadyExists));
+    }
+    fs.run_checked(
+        &format!("mkdir -m {:o} -- {}", u32::from(mode), shell_quote_path(path)),
+        RemoteErrorType::FileCreateDenied,
+    )?;
+    Ok(())
+}
+
+pub fn symlink(fs: &impl ShellFs, path: &Path, target: &Path) -> RemoteResult<()> {
+    fs.run_checked(
+        &format!("ln -s -- {} {}", shell_quote_path(target), shell_quote_path(path)),
+        RemoteErrorType::FileCreateDenied,
+    )?;
+    Ok(())
+}
+
+pub fn copy(fs: &impl ShellFs, src: &Path, dest: &Path) -> RemoteResult<()> {
+    let (src_q, dest_q) = (shell_quote_path(src), shell_quote_path(dest));
+    fs.run_checked(&format!("cp -a -- {} {}", src_q, dest_q), RemoteErrorType::FileCreateDenied)?;
+    Ok(())
+}
+
+pub fn mov(fs: &impl ShellFs, src: &Path, dest: &Path) -> RemoteResult<()> {
+    let (src_q, dest_q) = (shell_quote_path(src), shell_quote_path(dest));
+    fs.run_checked(&format!("mv -- {} {}", src_q, dest_q), RemoteErrorType::FileCreateDenied)?;
+    Ok(())
+}
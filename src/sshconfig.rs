@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use glob_match::glob_match;
+
+/// The directives of an OpenSSH client config that we apply when connecting to a host.
+#[derive(Debug, Default, Clone)]
+pub struct SshConfigParams {
+    pub host_name:     Option<String>,
+    pub user:          Option<String>,
+    pub port:          Option<u16>,
+    pub identity_file: Option<PathBuf>,
+    pub proxy_jump:    Option<String>,
+}
+
+/// Read the ssh_config at `path` and collect the directives that apply to `host`.
+/// As with OpenSSH, the first value obtained for each directive wins.
+/// `Match` blocks and `Include` are not supported, and are skipped.
+pub fn query(path: &Path, host: &str) -> Result<SshConfigParams, anyhow::Error> {
+    let body = std::fs::read_to_string(path).with_context(|| format!("Failed to read ssh config {:?}", path))?;
+
+    let mut params = SshConfigParams::default();
+    // Directives before the first Host line apply to every host.
+    let mut applies = true;
+
+    for line in body.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (keyword, args) = match line.find(|c: char| c.is_whitespace() || c == '=') {
+            Some(i) => (&line[..i], line[i..].trim_start_matches(|c: char| c.is_whitespace() || c == '=')),
+            None => (line, ""),
+        };
+        let args = args.trim().trim_matches('"');
+
+        match keyword.to_lowercase().as_str() {
+            "host" => applies = host_matches(host, args),
+            "match" => applies = false,
+            _ if !applies => {}
+            "hostname" => {
+                params.host_name.get_or_insert_with(|| args.replace("%h", host));
+            }
+            "user" => {
+                params.user.get_or_insert_with(|| args.to_string());
+            }
+            "port" => {
+                if params.port.is_none() {
+                    params.port = Some(args.parse().with_context(|| format!("Invalid Port {} in {:?}", args, path))?);
+                }
+            }
+            "identityfile" => {
+                params.identity_file.get_or_insert_with(|| expand_tilde(args));
+            }
+            "proxyjump" => {
+                if !args.eq_ignore_ascii_case("none") {
+                    params.proxy_jump.get_or_insert_with(|| args.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(params)
+}
+
+/// Whether `host` matches a Host line's patterns: at least one positive pattern, and no negated ones.
+fn host_matches(host: &str, patterns: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split_whitespace() {
+        match pattern.strip_prefix('!') {
+            Some(pattern) if glob_match(pattern, host) => return false,
+            Some(_) => {}
+            None => matched |= glob_match(pattern, host),
+        }
+    }
+    matched
+}
+
+pub fn expand_tilde(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/")
+        && let Some(home) = std::env::var_os("HOME")
+    {
+        return PathBuf::from(home).join(rest);
+    }
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn query_str(body: &str, host: &str) -> SshConfigParams {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(body.as_bytes()).unwrap();
+        query(file.path(), host).unwrap()
+    }
+
+    #[test]
+    fn host_patterns_match_globs() {
+        assert!(host_matches("web-01", "web-*"));
+        assert!(host_matches("db-01", "web-* db-*"));
+        assert!(!host_matches("cache-01", "web-* db-*"));
+    }
+
+    #[test]
+    fn negated_host_patterns_exclude() {
+        assert!(host_matches("web-01", "web-* !web-02"));
+        assert!(!host_matches("web-02", "web-* !web-02"));
+        // A negated pattern never matches on its own.
+        assert!(!host_matches("web-01", "!web-02"));
+    }
+
+    #[test]
+    fn first_value_wins() {
+        let params = query_str(
+            "Host web-01\n  User deploy\n  Port 2222\n\nHost *\n  User root\n  Port 22\n  IdentityFile /keys/id\n",
+            "web-01",
+        );
+        assert_eq!(params.user.as_deref(), Some("deploy"));
+        assert_eq!(params.port, Some(2222));
+        assert_eq!(params.identity_file, Some(PathBuf::from("/keys/id")));
+    }
+
+    #[test]
+    fn skips_blocks_that_do_not_apply() {
+        let params = query_str(
+            "Host * !bastion\n  ProxyJump bastion\n\n\
+             Match host web-01\n  User other\n\n\
+             Host web-*\n  HostName %h.internal\n",
+            "web-01",
+        );
+        assert_eq!(params.proxy_jump.as_deref(), Some("bastion"));
+        assert_eq!(params.user, None);
+        assert_eq!(params.host_name.as_deref(), Some("web-01.internal"));
+
+        let params = query_str("Host * !bastion\n  ProxyJump bastion\n", "bastion");
+        assert_eq!(params.proxy_jump, None);
+    }
+}
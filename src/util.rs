@@ -0,0 +1,16 @@
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// Quote `s` for safe interpolation into a POSIX sh command line.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+pub fn shell_quote_path(path: &Path) -> String {
+    shell_quote(&path.to_string_lossy())
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}